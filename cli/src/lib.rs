@@ -0,0 +1,187 @@
+//! Host side of the pomodoro serial protocol
+//!
+//! [`Client`] sends [`Request`]s to the device and skips anything that isn't a response frame,
+//! i.e. the shell prompt and the JSON event stream.
+use pomodoro_protocol::{
+    decode, encode, Config, FrameReader, Input, Request, Response, State, Stats, Status,
+    MAX_FRAME_SIZE,
+};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    thread,
+    time::Duration,
+};
+
+/// Errors from talking to the device
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The response could not be decoded
+    Decode(postcard::Error),
+    /// The device rejected the request
+    Device(pomodoro_protocol::Error),
+    /// The device answered with a response of the wrong kind
+    Unexpected(Response),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Decode(e) => write!(f, "invalid response: {e}"),
+            Self::Device(e) => write!(f, "{}", e.message()),
+            Self::Unexpected(r) => write!(f, "unexpected response: {r:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<postcard::Error> for Error {
+    fn from(e: postcard::Error) -> Self {
+        Error::Decode(e)
+    }
+}
+
+/// Sends requests over a serial port
+pub struct Client<P> {
+    port: P,
+    reader: FrameReader<MAX_FRAME_SIZE>,
+}
+
+impl<P: Read + Write> Client<P> {
+    pub fn new(port: P) -> Self {
+        Client {
+            port,
+            reader: FrameReader::new(),
+        }
+    }
+
+    /// Sends a request and waits for the response
+    pub fn request(&mut self, request: &Request) -> Result<Response, Error> {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        self.port.write_all(encode(request, &mut buf)?)?;
+        self.port.flush()?;
+
+        let mut byte = [0u8; 1];
+        loop {
+            self.port.read_exact(&mut byte)?;
+            if let Input::Frame(frame) = self.reader.push(byte[0]) {
+                return match decode(frame)? {
+                    Response::Error(e) => Err(Error::Device(e)),
+                    response => Ok(response),
+                };
+            }
+        }
+    }
+
+    /// Saves a timer config
+    pub fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        match self.request(&Request::SetConfig(config))? {
+            Response::Ok => Ok(()),
+            response => Err(Error::Unexpected(response)),
+        }
+    }
+
+    /// Reads the state and remaining time
+    pub fn status(&mut self) -> Result<Status, Error> {
+        match self.request(&Request::GetStatus)? {
+            Response::Status(status) => Ok(status),
+            response => Err(Error::Unexpected(response)),
+        }
+    }
+
+    /// Reads the session stats
+    pub fn stats(&mut self) -> Result<Stats, Error> {
+        match self.request(&Request::GetStats)? {
+            Response::Stats(stats) => Ok(stats),
+            response => Err(Error::Unexpected(response)),
+        }
+    }
+}
+
+/// Parses a duration in minutes: `50m`, `1h`, `1h30m` or a plain number of minutes
+pub fn parse_minutes(s: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid duration `{s}`, expected e.g. `50m` or `1h`");
+    if let Ok(minutes) = s.parse() {
+        return Ok(minutes);
+    }
+
+    let mut minutes = 0u32;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let value: u32 = rest[..digits].parse().map_err(|_| invalid())?;
+        let factor = match rest.as_bytes()[digits] {
+            b'h' => 60,
+            b'm' => 1,
+            _ => return Err(invalid()),
+        };
+        minutes = value
+            .checked_mul(factor)
+            .and_then(|v| minutes.checked_add(v))
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+    Ok(minutes)
+}
+
+/// Formats a status as `running 24:13`
+pub fn format_status(status: &Status) -> String {
+    let state = match status.state {
+        State::Paused => "paused",
+        State::Running => "running",
+        State::Resting => "resting",
+    };
+    format!(
+        "{state} {}:{:02}",
+        status.remaining / 60,
+        status.remaining % 60
+    )
+}
+
+/// Writes stats, either as text or as CSV with a header
+pub fn write_stats(out: &mut impl Write, stats: &Stats, csv: bool) -> io::Result<()> {
+    if csv {
+        writeln!(out, "work_completed,rest_completed,focus_minutes")?;
+        writeln!(
+            out,
+            "{},{},{}",
+            stats.work_completed,
+            stats.rest_completed,
+            stats.focus_time / 60
+        )
+    } else {
+        writeln!(out, "work phases: {}", stats.work_completed)?;
+        writeln!(out, "rest phases: {}", stats.rest_completed)?;
+        writeln!(out, "focus: {} mins", stats.focus_time / 60)
+    }
+}
+
+/// Writes the status every `interval`, `count` times or forever
+pub fn watch_status<P: Read + Write>(
+    client: &mut Client<P>,
+    out: &mut impl Write,
+    interval: Duration,
+    count: Option<usize>,
+) -> Result<(), Error> {
+    let mut n = 0;
+    loop {
+        writeln!(out, "{}", format_status(&client.status()?))?;
+        out.flush()?;
+        n += 1;
+        if count.is_some_and(|count| n >= count) {
+            return Ok(());
+        }
+        thread::sleep(interval);
+    }
+}
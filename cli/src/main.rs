@@ -0,0 +1,80 @@
+use clap::{Parser, Subcommand};
+use pomodoro_cli::{format_status, parse_minutes, watch_status, write_stats, Client};
+use pomodoro_protocol::Config;
+use std::{io, process::ExitCode, time::Duration};
+
+/// Companion tool for the micro:bit pomodoro timer
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Serial port of the micro:bit
+    #[arg(short, long, default_value = "/dev/ttyACM0")]
+    port: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Save a timer config, e.g. `set --work 50m --rest 10m`
+    Set {
+        #[arg(long, value_parser = parse_minutes)]
+        work: u32,
+        #[arg(long, value_parser = parse_minutes)]
+        rest: u32,
+    },
+    /// Show the state and remaining time
+    Status {
+        /// Keep showing the status every second
+        #[arg(long)]
+        watch: bool,
+        /// Stop watching after this many updates
+        #[arg(long, requires = "watch")]
+        count: Option<usize>,
+    },
+    /// Show the session stats
+    Stats {
+        /// Print CSV with a header row
+        #[arg(long)]
+        csv: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let port = serialport::new(&cli.port, 115_200)
+        .timeout(Duration::from_secs(2))
+        .open()?;
+    let mut client = Client::new(port);
+    let mut out = io::stdout().lock();
+
+    match cli.command {
+        Command::Set { work, rest } => {
+            let config = Config {
+                work_minutes: work,
+                rest_minutes: rest,
+            };
+            // Catch mistakes before talking to the device
+            config.validate().map_err(|e| e.message())?;
+            client.set_config(config)?;
+        }
+        Command::Status { watch: false, .. } => {
+            println!("{}", format_status(&client.status()?));
+        }
+        Command::Status { watch: true, count } => {
+            watch_status(&mut client, &mut out, Duration::from_secs(1), count)?;
+        }
+        Command::Stats { csv } => write_stats(&mut out, &client.stats()?, csv)?,
+    }
+    Ok(())
+}
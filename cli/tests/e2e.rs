@@ -0,0 +1,169 @@
+//! Round-trips the CLI and the protocol over a pseudo-terminal, against a stub device
+use pomodoro_cli::{watch_status, write_stats, Client, Error};
+use pomodoro_protocol::{
+    self as protocol, respond, Command, Config, Device, FrameReader, Input, State, Stats, Status,
+    MAX_FRAME_SIZE,
+};
+use serialport::{SerialPort, TTYPort};
+use std::{
+    io::{Read, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Stub device that stores what it is sent, standing in for the firmware
+#[derive(Debug)]
+struct SimDevice {
+    status: Status,
+    stats: Stats,
+    config: Config,
+}
+
+impl Device for SimDevice {
+    fn status(&self) -> Status {
+        self.status
+    }
+
+    fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    fn config(&self) -> Config {
+        self.config
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), protocol::Error> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn command(&mut self, command: Command) -> Result<(), protocol::Error> {
+        match command {
+            Command::Start => self.status.state = State::Running,
+            Command::Pause => self.status.state = State::Paused,
+            Command::Skip => self.stats.work_completed += 1,
+        }
+        Ok(())
+    }
+}
+
+/// Serves requests like the serial shell does, writing shell and event noise in between
+fn serve(mut port: TTYPort, device: Arc<Mutex<SimDevice>>) {
+    let mut reader = FrameReader::<MAX_FRAME_SIZE>::new();
+    let mut byte = [0u8; 1];
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    loop {
+        match port.read(&mut byte) {
+            Ok(1) => (),
+            // The client has hung up
+            _ => return,
+        }
+        if let Input::Frame(frame) = reader.push(byte[0]) {
+            let mut device = device.lock().unwrap();
+            // Tick, so `status --watch` sees the time going down
+            device.status.remaining -= 1;
+            let response = respond(frame, &mut *device, &mut buf);
+            port.write_all(b"{\"ts\":1,\"event\":{\"type\":\"boot\"}}\n")
+                .unwrap();
+            port.write_all(response).unwrap();
+            port.write_all(b"pomodoro> ").unwrap();
+        }
+    }
+}
+
+/// Opens a pseudo-terminal with a device serving the other end
+fn open() -> (TTYPort, Arc<Mutex<SimDevice>>) {
+    let (mut host, device_port) = TTYPort::pair().unwrap();
+    host.set_timeout(Duration::from_secs(2)).unwrap();
+    let device = Arc::new(Mutex::new(SimDevice {
+        status: Status {
+            state: State::Running,
+            remaining: 25 * 60,
+        },
+        stats: Stats {
+            work_completed: 3,
+            rest_completed: 2,
+            focus_time: 75 * 60 + 30,
+        },
+        config: Config {
+            work_minutes: 25,
+            rest_minutes: 5,
+        },
+    }));
+    let shared = device.clone();
+    thread::spawn(move || serve(device_port, shared));
+    (host, device)
+}
+
+fn connect() -> (Client<TTYPort>, Arc<Mutex<SimDevice>>) {
+    let (host, device) = open();
+    (Client::new(host), device)
+}
+
+#[test]
+fn set_saves_config() {
+    let (mut client, device) = connect();
+    let config = Config {
+        work_minutes: 50,
+        rest_minutes: 10,
+    };
+    client.set_config(config).unwrap();
+    assert_eq!(device.lock().unwrap().config, config);
+}
+
+#[test]
+fn set_reports_out_of_range_config() {
+    let (mut client, device) = connect();
+    let config = Config {
+        work_minutes: 50,
+        rest_minutes: 90,
+    };
+    match client.set_config(config) {
+        Err(Error::Device(protocol::Error::RestOutOfRange)) => (),
+        result => panic!("unexpected result: {result:?}"),
+    }
+    assert_eq!(device.lock().unwrap().config.rest_minutes, 5);
+}
+
+#[test]
+fn status_watch_prints_each_update() {
+    let (mut client, _) = connect();
+    let mut out = Vec::new();
+    watch_status(&mut client, &mut out, Duration::from_millis(10), Some(3)).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "running 24:59\nrunning 24:58\nrunning 24:57\n"
+    );
+}
+
+#[test]
+fn stats_as_csv() {
+    let (mut client, _) = connect();
+    let mut out = Vec::new();
+    write_stats(&mut out, &client.stats().unwrap(), true).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "work_completed,rest_completed,focus_minutes\n3,2,75\n"
+    );
+}
+
+#[test]
+fn device_rejects_garbage_frames() {
+    let (mut host, _) = open();
+
+    // A frame that doesn't decode, followed by text for the shell
+    host.write_all(&[0, 0x03, 0xFF, 0xFF, 0, b'h']).unwrap();
+    let mut reader = FrameReader::<MAX_FRAME_SIZE>::new();
+    let mut byte = [0u8; 1];
+    let response: protocol::Response = loop {
+        host.read_exact(&mut byte).unwrap();
+        if let Input::Frame(frame) = reader.push(byte[0]) {
+            break protocol::decode(frame).unwrap();
+        }
+    };
+    assert_eq!(
+        response,
+        protocol::Response::Error(protocol::Error::Malformed)
+    );
+}
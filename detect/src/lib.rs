@@ -0,0 +1,30 @@
+//! Gesture detection from sensor samples
+//!
+//! A [`ShakeDetector`] finds shakes in accelerometer [`Sample`]s, and a [`ShakeContext`] tells
+//! what a shake means in the current state of the timer. A [`NoiseDetector`] tells when the
+//! sound [`level`] of microphone bursts stays loud. The crate is `no_std` and shared by the
+//! firmware and host tests.
+#![no_std]
+
+mod noise;
+mod shake;
+pub use noise::*;
+pub use shake::*;
+
+/// Acceleration in mg, in the board's frame: x to the right, y towards the top edge and z out
+/// of the LED side
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+impl Sample {
+    /// Squared magnitude in mg². Even at full scale on every axis it fits in a `u32`.
+    pub fn magnitude_squared(&self) -> u32 {
+        let square = |axis: i16| (axis.unsigned_abs() as u32).pow(2);
+        square(self.x) + square(self.y) + square(self.z)
+    }
+}
@@ -0,0 +1,84 @@
+//! Noise detection from microphone bursts
+
+/// Noise detection thresholds
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoiseConfig {
+    /// Sound level in ADC counts above which the room is loud. Quiet rooms measure below 10.
+    pub threshold: u16,
+    /// Measurements in a row needed to change between quiet and loud
+    pub hold: u8,
+    /// Light the top-right LED while the room is loud
+    pub indicator: bool,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            threshold: 40,
+            hold: 10,
+            indicator: false,
+        }
+    }
+}
+
+/// Sound level of a burst of samples: the mean absolute deviation from their mean, in ADC
+/// counts. The mean removes the bias of the microphone.
+pub fn level(samples: &[i16]) -> u16 {
+    if samples.is_empty() {
+        return 0;
+    }
+    let n = samples.len() as i32;
+    let mean = samples.iter().map(|s| *s as i32).sum::<i32>() / n;
+    let deviation = samples
+        .iter()
+        .map(|s| (*s as i32 - mean).abs())
+        .sum::<i32>();
+    (deviation / n).min(u16::MAX as i32) as u16
+}
+
+/// Detects when the sound level stays above a threshold
+pub struct NoiseDetector {
+    config: NoiseConfig,
+    loud: bool,
+    /// Measurements in a row that disagree with `loud`
+    streak: u8,
+}
+
+impl NoiseDetector {
+    pub fn new(config: NoiseConfig) -> Self {
+        NoiseDetector {
+            config,
+            loud: false,
+            streak: 0,
+        }
+    }
+
+    /// Whether the room is loud
+    pub fn is_loud(&self) -> bool {
+        self.loud
+    }
+
+    /// Feeds a sound level. Returns whether the room is loud when it changes.
+    pub fn update(&mut self, level: u16) -> Option<bool> {
+        let threshold = self.config.threshold;
+        // Quiet again below 3/4 of the threshold, so a level around it doesn't flicker
+        let loud = if self.loud {
+            level >= threshold - threshold / 4
+        } else {
+            level > threshold
+        };
+
+        if loud == self.loud {
+            self.streak = 0;
+            return None;
+        }
+        self.streak += 1;
+        if self.streak < self.config.hold {
+            return None;
+        }
+        self.streak = 0;
+        self.loud = loud;
+        Some(loud)
+    }
+}
@@ -0,0 +1,144 @@
+//! Shake gesture
+use crate::Sample;
+use embassy_time::{Duration, Instant};
+use pomodoro_protocol::State;
+
+/// Shake detection thresholds
+#[derive(Clone, Copy, Debug)]
+pub struct ShakeConfig {
+    /// Magnitude of a peak in mg. The board at rest sees 1000 mg.
+    pub threshold: u16,
+    /// Peaks needed for a shake
+    pub peaks: u8,
+    /// Time in which the peaks have to happen
+    pub window: Duration,
+    /// Time after a shake in which no new shake is detected
+    pub cooldown: Duration,
+}
+
+impl Default for ShakeConfig {
+    fn default() -> Self {
+        ShakeConfig {
+            threshold: 2000,
+            peaks: 3,
+            window: Duration::from_millis(1000),
+            cooldown: Duration::from_millis(1500),
+        }
+    }
+}
+
+/// Detects a burst of high-magnitude peaks in a short window
+pub struct ShakeDetector {
+    config: ShakeConfig,
+    /// Whether the last sample was part of a peak
+    in_peak: bool,
+    /// Peaks seen since `first_peak`
+    peaks: u8,
+    first_peak: Instant,
+    last_shake: Option<Instant>,
+}
+
+impl ShakeDetector {
+    pub fn new(config: ShakeConfig) -> Self {
+        ShakeDetector {
+            config,
+            in_peak: false,
+            peaks: 0,
+            first_peak: Instant::from_ticks(0),
+            last_shake: None,
+        }
+    }
+
+    /// Feeds a sample. Returns `true` when a shake is complete.
+    pub fn update(&mut self, sample: &Sample, now: Instant) -> bool {
+        let magnitude = sample.magnitude_squared();
+        let threshold = self.config.threshold as u32;
+        // A peak ends at 3/4 of the threshold, so noise around the threshold isn't counted twice
+        let release = threshold * 3 / 4;
+
+        if self.in_peak {
+            self.in_peak = magnitude >= release * release;
+            return false;
+        }
+        if magnitude < threshold * threshold {
+            return false;
+        }
+        self.in_peak = true;
+
+        if let Some(last) = self.last_shake {
+            if now - last < self.config.cooldown {
+                return false;
+            }
+        }
+
+        if self.peaks == 0 || now - self.first_peak > self.config.window {
+            self.peaks = 0;
+            self.first_peak = now;
+        }
+        self.peaks += 1;
+
+        if self.peaks < self.config.peaks {
+            return false;
+        }
+        self.peaks = 0;
+        self.last_shake = Some(now);
+        true
+    }
+}
+
+/// What a shake asks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShakeAction {
+    /// Acknowledge the alarm
+    Acknowledge,
+    /// Skip to the next phase
+    Skip,
+    /// Resume the work phase
+    Resume,
+}
+
+/// Tracks the app state to tell what a shake means
+pub struct ShakeContext {
+    state: State,
+    previous: Option<State>,
+    since: Instant,
+}
+
+impl ShakeContext {
+    /// Time after a pause in which a shake resumes the work phase
+    pub const UNDO_WINDOW: Duration = Duration::from_secs(10);
+
+    pub fn new(state: State, now: Instant) -> Self {
+        ShakeContext {
+            state,
+            previous: None,
+            since: now,
+        }
+    }
+
+    /// Records the current state
+    pub fn observe(&mut self, state: State, now: Instant) {
+        if state != self.state {
+            self.previous = Some(self.state);
+            self.state = state;
+            self.since = now;
+        }
+    }
+
+    /// Action for a shake: acknowledge the alarm while working, skip a rest, or undo a recent
+    /// pause
+    pub fn action(&self, now: Instant) -> Option<ShakeAction> {
+        match self.state {
+            State::Running => Some(ShakeAction::Acknowledge),
+            State::Resting => Some(ShakeAction::Skip),
+            State::Paused
+                if self.previous == Some(State::Running)
+                    && now - self.since <= Self::UNDO_WINDOW =>
+            {
+                Some(ShakeAction::Resume)
+            }
+            State::Paused => None,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use pomodoro_detect::{level, NoiseConfig, NoiseDetector};
+
+#[test]
+fn noise_level_ignores_bias() {
+    assert_eq!(level(&[]), 0);
+    assert_eq!(level(&[1800; 64]), 0);
+
+    let mut square = [0i16; 64];
+    for (i, s) in square.iter_mut().enumerate() {
+        *s = if i % 8 < 4 { 1900 } else { 1700 };
+    }
+    assert_eq!(level(&square), 100);
+}
+
+#[test]
+fn noise_detector_needs_a_loud_stretch() {
+    let config = NoiseConfig {
+        threshold: 40,
+        hold: 3,
+        indicator: false,
+    };
+    let mut detector = NoiseDetector::new(config);
+
+    // A short burst isn't enough
+    assert_eq!(detector.update(100), None);
+    assert_eq!(detector.update(100), None);
+    assert_eq!(detector.update(5), None);
+
+    assert_eq!(detector.update(50), None);
+    assert_eq!(detector.update(50), None);
+    assert_eq!(detector.update(50), Some(true));
+    assert!(detector.is_loud());
+
+    // Levels just below the threshold keep the room loud
+    for _ in 0..5 {
+        assert_eq!(detector.update(35), None);
+    }
+    assert_eq!(detector.update(10), None);
+    assert_eq!(detector.update(10), None);
+    assert_eq!(detector.update(10), Some(false));
+}
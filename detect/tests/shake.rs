@@ -0,0 +1,101 @@
+//! Shakes in recorded accelerometer samples
+use embassy_time::Instant;
+use pomodoro_detect::{Sample, ShakeAction, ShakeConfig, ShakeContext, ShakeDetector};
+use pomodoro_protocol::State;
+
+/// Three swings of a hand-held board, sampled every 20 ms
+const SHAKE: [(i16, i16, i16); 18] = [
+    (0, 0, 1000),
+    (200, 0, 980),
+    (1400, 100, 900),
+    (2600, 300, 700),
+    (3100, 200, 600),
+    (1000, 0, 900),
+    (-900, -100, 950),
+    (-2500, -300, 800),
+    (-3000, -200, 700),
+    (-1100, 0, 1000),
+    (800, 100, 900),
+    (2400, 200, 800),
+    (2900, 100, 700),
+    (900, 0, 950),
+    (-1000, 0, 1000),
+    (-2700, -200, 800),
+    (-1500, 0, 900),
+    (0, 0, 1000),
+];
+
+/// The board put down on a table with a knock, sampled every 20 ms
+const KNOCK: [(i16, i16, i16); 5] = [
+    (0, 0, 1000),
+    (100, 0, 1800),
+    (300, 0, 2600),
+    (0, 0, 1100),
+    (0, 0, 1000),
+];
+
+/// Returns the times of the shakes, with a sample every `period` ms
+fn detect_shakes<'a>(samples: impl Iterator<Item = &'a (i16, i16, i16)>, period: u64) -> Vec<u64> {
+    let mut shakes = ShakeDetector::new(ShakeConfig::default());
+    let mut found = Vec::new();
+    for (i, &(x, y, z)) in samples.enumerate() {
+        let t = i as u64 * period;
+        if shakes.update(&Sample { x, y, z }, Instant::from_millis(t)) {
+            found.push(t);
+        }
+    }
+    found
+}
+
+#[test]
+fn shake_is_detected_on_third_peak() {
+    assert_eq!(detect_shakes(SHAKE.iter(), 20), [220]);
+    // A long shake only counts once
+    assert_eq!(detect_shakes(SHAKE.iter().chain(SHAKE.iter()), 20), [220]);
+}
+
+#[test]
+fn shakes_after_the_cooldown_count_again() {
+    let pause = [(0, 0, 1000); 75];
+    let samples = SHAKE.iter().chain(pause.iter()).chain(SHAKE.iter());
+    let second = (SHAKE.len() + pause.len()) as u64 * 20 + 220;
+    assert_eq!(detect_shakes(samples, 20), [220, second]);
+}
+
+#[test]
+fn shake_ignores_knocks_and_slow_swings() {
+    assert!(detect_shakes(KNOCK.iter(), 20).is_empty());
+    assert!(detect_shakes(SHAKE.iter(), 200).is_empty());
+}
+
+#[test]
+fn magnitude_holds_full_scale_samples() {
+    let sample = |x, y, z| Sample { x, y, z };
+    assert_eq!(sample(3, -4, 0).magnitude_squared(), 25);
+    assert_eq!(
+        sample(i16::MIN, i16::MIN, i16::MIN).magnitude_squared(),
+        3 << 30
+    );
+    assert_eq!(
+        sample(i16::MAX, i16::MIN, i16::MAX).magnitude_squared(),
+        2 * 32767 * 32767 + (1 << 30)
+    );
+}
+
+#[test]
+fn shake_action_depends_on_state() {
+    let t = Instant::from_secs;
+    let mut context = ShakeContext::new(State::Paused, t(0));
+    // Paused at boot
+    assert_eq!(context.action(t(1)), None);
+
+    context.observe(State::Running, t(1));
+    assert_eq!(context.action(t(2)), Some(ShakeAction::Acknowledge));
+
+    context.observe(State::Paused, t(100));
+    assert_eq!(context.action(t(105)), Some(ShakeAction::Resume));
+    assert_eq!(context.action(t(111)), None);
+
+    context.observe(State::Resting, t(200));
+    assert_eq!(context.action(t(201)), Some(ShakeAction::Skip));
+}
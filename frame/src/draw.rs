@@ -0,0 +1,245 @@
+//! Drawing on frames
+//!
+//! Shapes are clipped to the frame, so they can run over the edges. Everything here is a
+//! `const fn`, to build icons at compile time.
+use crate::{Bitmap, Frame};
+
+/// Direction of the bars of a chart, see [`Frame::bar_chart`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bars {
+    /// One column per value, growing up from the bottom row
+    Columns,
+    /// One row per value, growing right from the left column
+    Rows,
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> Frame<XSIZE, YSIZE, WORDS> {
+    /// Create a bar chart with a bar per value. Values past the frame are dropped, and bars
+    /// longer than the frame are cut off.
+    pub const fn bar_chart(values: &[usize], bars: Bars) -> Self {
+        let mut frame = Self::empty();
+        let (count, max) = match bars {
+            Bars::Columns => (XSIZE, YSIZE),
+            Bars::Rows => (YSIZE, XSIZE),
+        };
+        let mut i = 0;
+        while i < values.len() && i < count {
+            let length = if values[i] < max { values[i] } else { max };
+            let (bar, length) = (i as isize, length as isize);
+            match bars {
+                Bars::Columns => frame.fill_rect(bar, YSIZE as isize - length, 1, length),
+                Bars::Rows => frame.fill_rect(0, bar, length, 1),
+            }
+            i += 1;
+        }
+        frame
+    }
+
+    /// Set or clear (x, y), if it is on the frame
+    pub const fn plot(&mut self, x: isize, y: isize, on: bool) {
+        if x < 0 || y < 0 || x >= XSIZE as isize || y >= YSIZE as isize {
+            return;
+        }
+        match on {
+            true => self.set(x as usize, y as usize),
+            false => self.unset(x as usize, y as usize),
+        }
+    }
+
+    /// Draw a line from (x0, y0) to (x1, y1), both ends included
+    pub const fn line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize) {
+        // Only the part on the frame is stepped through, so long lines end quickly
+        let (x0, y0, x1, y1) = match clip_line::<XSIZE, YSIZE>(x0, y0, x1, y1) {
+            Some(ends) => ends,
+            None => return,
+        };
+        // Bresenham's line algorithm, stepping x and y by the error to the ideal line
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+        loop {
+            self.plot(x, y, true);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draw the outline of a `width` x `height` rectangle with its top left corner at (x, y)
+    pub const fn rect(&mut self, x: isize, y: isize, width: isize, height: isize) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        let (right, bottom) = (x.saturating_add(width - 1), y.saturating_add(height - 1));
+        self.line(x, y, right, y);
+        self.line(x, bottom, right, bottom);
+        self.line(x, y, x, bottom);
+        self.line(right, y, right, bottom);
+    }
+
+    /// Fill a `width` x `height` rectangle with its top left corner at (x, y)
+    pub const fn fill_rect(&mut self, x: isize, y: isize, width: isize, height: isize) {
+        let (left, right) = (clamp(x, XSIZE), clamp(x.saturating_add(width), XSIZE));
+        let (top, bottom) = (clamp(y, YSIZE), clamp(y.saturating_add(height), YSIZE));
+        let mut row = top;
+        while row < bottom {
+            let mut col = left;
+            while col < right {
+                self.set(col as usize, row as usize);
+                col += 1;
+            }
+            row += 1;
+        }
+    }
+
+    /// Turn all pixels on that are off, and off that are on
+    pub const fn invert(&mut self) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].invert();
+            y += 1;
+        }
+    }
+
+    /// Logical XOR with another frame
+    pub const fn xor(&mut self, other: &Frame<XSIZE, YSIZE, WORDS>) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].xor(&other.bitmap[y]);
+            y += 1;
+        }
+    }
+
+    /// Shift all rows up by n rows. The bottom rows are cleared.
+    pub const fn shift_up(&mut self, n: usize) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y] = match n < YSIZE - y {
+                true => self.bitmap[y + n],
+                false => Bitmap::empty(XSIZE),
+            };
+            y += 1;
+        }
+    }
+
+    /// Shift all rows down by n rows. The top rows are cleared.
+    pub const fn shift_down(&mut self, n: usize) {
+        let mut y = YSIZE;
+        while y > 0 {
+            y -= 1;
+            self.bitmap[y] = match n <= y {
+                true => self.bitmap[y - n],
+                false => Bitmap::empty(XSIZE),
+            };
+        }
+    }
+
+    /// Copy another frame with its top left corner at (x, y). Pixels under the copy are
+    /// replaced, and pixels that fall off this frame are dropped.
+    pub const fn copy<const W: usize, const H: usize, const SOURCE_WORDS: usize>(
+        &mut self,
+        source: &Frame<W, H, SOURCE_WORDS>,
+        x: isize,
+        y: isize,
+    ) {
+        let mut row = 0;
+        while row < H {
+            let mut col = 0;
+            while col < W {
+                let on = source.is_set(col, row);
+                self.plot(
+                    x.saturating_add(col as isize),
+                    y.saturating_add(row as isize),
+                    on,
+                );
+                col += 1;
+            }
+            row += 1;
+        }
+    }
+}
+
+/// `value` limited to `0..=max`
+const fn clamp(value: isize, max: usize) -> isize {
+    if value < 0 {
+        0
+    } else if value > max as isize {
+        max as isize
+    } else {
+        value
+    }
+}
+
+/// Largest coordinate magnitude [`clip_line`] works with, so its products fit in an `i128`
+const MAX_COORDINATE: i128 = 1 << 61;
+
+/// Ends of the part of a line on an `XSIZE` x `YSIZE` frame, or `None` if it misses the frame.
+/// The ends are where the line crosses the edges, rounded to the nearest pixel (Liang-Barsky).
+/// Coordinates are first limited to [`MAX_COORDINATE`], which only bends lines with ends that
+/// far out.
+const fn clip_line<const XSIZE: usize, const YSIZE: usize>(
+    x0: isize,
+    y0: isize,
+    x1: isize,
+    y1: isize,
+) -> Option<(isize, isize, isize, isize)> {
+    if XSIZE == 0 || YSIZE == 0 {
+        return None;
+    }
+    let (x0, y0, x1, y1) = (limit(x0), limit(y0), limit(x1), limit(y1));
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (max_x, max_y) = (XSIZE as i128 - 1, YSIZE as i128 - 1);
+    // Each edge keeps the points where `p * t <= q`, for t from 0 at (x0, y0) to 1 at (x1, y1)
+    let edges = [(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)];
+    // Fractions of the line where the part on the frame starts and ends
+    let (mut start, mut end) = ((0, 1), (1, 1));
+    let mut i = 0;
+    while i < edges.len() {
+        let (p, q) = edges[i];
+        if p == 0 && q < 0 {
+            return None;
+        } else if p < 0 && -q * start.1 > start.0 * -p {
+            start = (-q, -p);
+        } else if p > 0 && q * end.1 < end.0 * p {
+            end = (q, p);
+        }
+        i += 1;
+    }
+    if start.0 * end.1 > end.0 * start.1 {
+        return None;
+    }
+
+    // Nearest pixel to a fraction of the line
+    const fn at(from: i128, delta: i128, (n, d): (i128, i128)) -> isize {
+        (from + (2 * n * delta + d).div_euclid(2 * d)) as isize
+    }
+    Some((
+        at(x0, dx, start),
+        at(y0, dy, start),
+        at(x0, dx, end),
+        at(y0, dy, end),
+    ))
+}
+
+/// `value` limited to `-MAX_COORDINATE..=MAX_COORDINATE`
+const fn limit(value: isize) -> i128 {
+    let value = value as i128;
+    if value < -MAX_COORDINATE {
+        -MAX_COORDINATE
+    } else if value > MAX_COORDINATE {
+        MAX_COORDINATE
+    } else {
+        value
+    }
+}
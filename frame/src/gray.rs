@@ -0,0 +1,148 @@
+//! Grayscale frames
+use crate::{Frame, Rotation};
+
+/// Bits of a gray level
+pub const GRAY_BITS: usize = 3;
+/// Brightest gray level. Level 0 is off.
+pub const MAX_LEVEL: u8 = (1 << GRAY_BITS) - 1;
+
+/// An NxM frame with a gray level per pixel, kept as one binary frame per bit of the level.
+/// A driver shows each of these bit planes for a time weighted by its bit (bit-angle
+/// modulation).
+#[derive(Clone, Copy, PartialEq)]
+pub struct GrayFrame<const XSIZE: usize, const YSIZE: usize, const WORDS: usize = 1> {
+    planes: [Frame<XSIZE, YSIZE, WORDS>; GRAY_BITS],
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> core::fmt::Debug
+    for GrayFrame<XSIZE, YSIZE, WORDS>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for y in 0..YSIZE {
+            for x in 0..XSIZE {
+                write!(f, "{}", self.level(x, y))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> defmt::Format
+    for GrayFrame<XSIZE, YSIZE, WORDS>
+{
+    fn format(&self, f: defmt::Formatter<'_>) {
+        for y in 0..YSIZE {
+            for x in 0..XSIZE {
+                defmt::write!(f, "{}", self.level(x, y));
+            }
+            defmt::write!(f, "\n");
+        }
+    }
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> GrayFrame<XSIZE, YSIZE, WORDS> {
+    /// Create an empty frame
+    pub const fn empty() -> Self {
+        Self {
+            planes: [Frame::empty(); GRAY_BITS],
+        }
+    }
+
+    /// Create a frame with the pixels of a binary frame at a level
+    pub fn from_frame(frame: &Frame<XSIZE, YSIZE, WORDS>, level: u8) -> Self {
+        let mut gray = Self::empty();
+        for (bit, plane) in gray.planes.iter_mut().enumerate() {
+            if level.min(MAX_LEVEL) & (1 << bit) != 0 {
+                *plane = *frame;
+            }
+        }
+        gray
+    }
+
+    /// Clear this frame (empty)
+    pub fn clear(&mut self) {
+        for plane in self.planes.iter_mut() {
+            plane.clear();
+        }
+    }
+
+    /// Set the level of (x, y), up to [`MAX_LEVEL`]
+    pub fn set(&mut self, x: usize, y: usize, level: u8) {
+        let level = level.min(MAX_LEVEL);
+        for (bit, plane) in self.planes.iter_mut().enumerate() {
+            if level & (1 << bit) != 0 {
+                plane.set(x, y);
+            } else {
+                plane.unset(x, y);
+            }
+        }
+    }
+
+    /// Level of (x, y)
+    pub fn level(&self, x: usize, y: usize) -> u8 {
+        self.planes
+            .iter()
+            .enumerate()
+            .filter(|(_, plane)| plane.is_set(x, y))
+            .map(|(bit, _)| 1 << bit)
+            .sum()
+    }
+
+    /// Pixels lit while bit `bit` of the levels is shown
+    pub fn plane(&self, bit: usize) -> &Frame<XSIZE, YSIZE, WORDS> {
+        &self.planes[bit]
+    }
+
+    /// Scale all levels by `level / MAX_LEVEL`, rounded to the nearest level. Scaling by
+    /// [`MAX_LEVEL`] keeps the frame, 0 clears it.
+    pub fn scale(&mut self, level: u8) {
+        let level = level.min(MAX_LEVEL) as u16;
+        let max = MAX_LEVEL as u16;
+        for y in 0..YSIZE {
+            for x in 0..XSIZE {
+                let scaled = (self.level(x, y) as u16 * level + max / 2) / max;
+                self.set(x, y, scaled as u8);
+            }
+        }
+    }
+
+    /// Rotate clockwise, see [`Frame::rotate`]
+    pub fn rotate(&mut self, rotation: Rotation) {
+        for plane in self.planes.iter_mut() {
+            plane.rotate(rotation);
+        }
+    }
+
+    /// Mirror left to right
+    pub fn mirror(&mut self) {
+        for plane in self.planes.iter_mut() {
+            plane.mirror();
+        }
+    }
+
+    /// Flip upside down
+    pub fn flip(&mut self) {
+        for plane in self.planes.iter_mut() {
+            plane.flip();
+        }
+    }
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> Default
+    for GrayFrame<XSIZE, YSIZE, WORDS>
+{
+    fn default() -> Self {
+        GrayFrame::empty()
+    }
+}
+
+/// Binary frames are fully lit
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> From<Frame<XSIZE, YSIZE, WORDS>>
+    for GrayFrame<XSIZE, YSIZE, WORDS>
+{
+    fn from(frame: Frame<XSIZE, YSIZE, WORDS>) -> Self {
+        Self::from_frame(&frame, MAX_LEVEL)
+    }
+}
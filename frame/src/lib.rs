@@ -0,0 +1,393 @@
+//! Frames for LED matrix displays
+//!
+//! A [`Frame`] is a row-major grid of on/off pixels, one [`Bitmap`] per row. Rows are packed
+//! into `WORDS` bytes, first pixel in the most significant bit, so they can be up to
+//! [`Bitmap::CAPACITY`] pixels wide. One byte, for up to 8 pixels, is the default. A
+//! [`GrayFrame`] has [`MAX_LEVEL`] + 1 brightness levels per pixel. The crate is `no_std` and
+//! shared by the firmware and host tests.
+#![no_std]
+
+mod draw;
+mod gray;
+pub use draw::*;
+pub use gray::*;
+
+// Using u8 for each word
+const BITMAP_WORD_SIZE: usize = 8;
+
+/// A bitmap of `WORDS` bytes used by Frame to create a compact frame buffer. Bits past the
+/// width are always clear.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Bitmap<const WORDS: usize = 1> {
+    data: [u8; WORDS],
+    nbits: usize,
+}
+
+impl<const WORDS: usize> core::fmt::Debug for Bitmap<WORDS> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for i in 0..self.nbits {
+            if self.is_set(i) {
+                write!(f, "1")?;
+            } else {
+                write!(f, "0")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const WORDS: usize> defmt::Format for Bitmap<WORDS> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        for i in 0..self.nbits {
+            if self.is_set(i) {
+                defmt::write!(f, "1");
+            } else {
+                defmt::write!(f, "0");
+            }
+        }
+    }
+}
+
+impl<const WORDS: usize> Bitmap<WORDS> {
+    /// Widest bitmap, and so the widest frame, with `WORDS` words
+    pub const CAPACITY: usize = WORDS * BITMAP_WORD_SIZE;
+
+    /// Create a new bitmap with initial input and number of bits. Bitmaps of up to 8 bits take
+    /// the lowest bits of the input, wider ones start with the input.
+    pub const fn new(input: u8, nbits: usize) -> Self {
+        let mut bitmap = Self::empty(nbits);
+        if nbits < BITMAP_WORD_SIZE {
+            bitmap.data[0] = match input.checked_shl((BITMAP_WORD_SIZE - nbits) as u32) {
+                Some(word) => word,
+                None => 0,
+            };
+        } else {
+            bitmap.data[0] = input;
+        }
+        bitmap
+    }
+
+    /// Create a bitmap from the lowest `nbits` bits of the input, first bit in the most
+    /// significant place
+    pub const fn from_bits(input: u32, nbits: usize) -> Self {
+        let mut bitmap = Self::empty(nbits);
+        let mut bit = 0;
+        while bit < nbits {
+            if (input >> (nbits - 1 - bit)) & 1 == 1 {
+                bitmap.data[bit / BITMAP_WORD_SIZE] |=
+                    1 << ((BITMAP_WORD_SIZE - 1) - bit % BITMAP_WORD_SIZE);
+            }
+            bit += 1;
+        }
+        bitmap
+    }
+
+    /// Create an empty bitmap with nbits bits
+    pub const fn empty(nbits: usize) -> Self {
+        assert!(nbits <= Self::CAPACITY, "bitmap is wider than its words");
+        Self {
+            data: [0; WORDS],
+            nbits,
+        }
+    }
+
+    /// Number of bits
+    pub const fn len(&self) -> usize {
+        self.nbits
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.nbits == 0
+    }
+
+    /// Set bit n in bitmap
+    pub const fn set(&mut self, bit: usize) {
+        assert!(bit < self.nbits);
+        let idx: usize = bit / BITMAP_WORD_SIZE;
+        let p: usize = bit % BITMAP_WORD_SIZE;
+        self.data[idx] |= 1 << ((BITMAP_WORD_SIZE - 1) - p);
+    }
+
+    /// Clear all bits in bitmap
+    pub const fn clear_all(&mut self) {
+        let mut i = 0;
+        while i < WORDS {
+            self.data[i] = 0;
+            i += 1;
+        }
+    }
+
+    /// Clear bit n in bitmap
+    pub const fn clear(&mut self, bit: usize) {
+        assert!(bit < self.nbits);
+        let idx: usize = bit / BITMAP_WORD_SIZE;
+        let p: usize = bit % BITMAP_WORD_SIZE;
+        self.data[idx] &= !(1 << ((BITMAP_WORD_SIZE - 1) - p));
+    }
+
+    /// Check if bit n is set in bitmap
+    pub const fn is_set(&self, bit: usize) -> bool {
+        assert!(bit < self.nbits);
+        let idx: usize = bit / BITMAP_WORD_SIZE;
+        let p: usize = bit % BITMAP_WORD_SIZE;
+        (self.data[idx] & (1 << ((BITMAP_WORD_SIZE - 1) - p))) != 0
+    }
+
+    /// Shift left by nbits bits, towards bit 0. Bits carry over from the next word.
+    pub const fn shift_left(&mut self, nbits: usize) {
+        let (words, bits) = (nbits / BITMAP_WORD_SIZE, nbits % BITMAP_WORD_SIZE);
+        let mut i = 0;
+        while i < WORDS {
+            let high = self.word(i.saturating_add(words));
+            let low = self.word(i.saturating_add(words).saturating_add(1));
+            self.data[i] = match bits {
+                0 => high,
+                _ => (high << bits) | (low >> (BITMAP_WORD_SIZE - bits)),
+            };
+            i += 1;
+        }
+    }
+
+    /// Shift right by nbits bits. Bits carry over into the next word, and bits shifted past
+    /// the width are dropped.
+    pub const fn shift_right(&mut self, nbits: usize) {
+        let (words, bits) = (nbits / BITMAP_WORD_SIZE, nbits % BITMAP_WORD_SIZE);
+        let mut i = WORDS;
+        while i > 0 {
+            i -= 1;
+            let low = match i.checked_sub(words) {
+                Some(j) => self.data[j],
+                None => 0,
+            };
+            let high = match i.checked_sub(words.saturating_add(1)) {
+                Some(j) => self.data[j],
+                None => 0,
+            };
+            self.data[i] = match bits {
+                0 => low,
+                _ => (low >> bits) | (high << (BITMAP_WORD_SIZE - bits)),
+            };
+        }
+        self.clear_padding();
+    }
+
+    /// Logical OR with another bitmap
+    pub const fn or(&mut self, other: &Bitmap<WORDS>) {
+        let mut i = 0;
+        while i < WORDS {
+            self.data[i] |= other.data[i];
+            i += 1;
+        }
+        self.clear_padding();
+    }
+
+    /// Logical AND with another bitmap
+    pub const fn and(&mut self, other: &Bitmap<WORDS>) {
+        let mut i = 0;
+        while i < WORDS {
+            self.data[i] &= other.data[i];
+            i += 1;
+        }
+    }
+
+    /// Word i, or 0 past the end
+    const fn word(&self, i: usize) -> u8 {
+        if i < WORDS {
+            self.data[i]
+        } else {
+            0
+        }
+    }
+
+    /// Logical XOR with another bitmap
+    pub const fn xor(&mut self, other: &Bitmap<WORDS>) {
+        let mut i = 0;
+        while i < WORDS {
+            self.data[i] ^= other.data[i];
+            i += 1;
+        }
+        self.clear_padding();
+    }
+
+    /// Flip all bits
+    pub const fn invert(&mut self) {
+        let mut i = 0;
+        while i < WORDS {
+            self.data[i] = !self.data[i];
+            i += 1;
+        }
+        self.clear_padding();
+    }
+
+    /// Clear the bits past the width
+    const fn clear_padding(&mut self) {
+        let mut i = 0;
+        while i < WORDS {
+            self.data[i] &= match self.nbits.saturating_sub(i * BITMAP_WORD_SIZE) {
+                0 => 0,
+                n if n >= BITMAP_WORD_SIZE => 0xFF,
+                n => !(0xFF >> n),
+            };
+            i += 1;
+        }
+    }
+}
+
+/// Clockwise rotation of a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+/// An NxM frame that can be displayed on a LED matrix. Rows are `WORDS` bytes, so frames are
+/// up to [`Bitmap::CAPACITY`] pixels wide.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Frame<const XSIZE: usize, const YSIZE: usize, const WORDS: usize = 1> {
+    bitmap: [Bitmap<WORDS>; YSIZE],
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> core::fmt::Debug
+    for Frame<XSIZE, YSIZE, WORDS>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for b in self.bitmap.iter() {
+            writeln!(f, "{:?}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> defmt::Format
+    for Frame<XSIZE, YSIZE, WORDS>
+{
+    fn format(&self, f: defmt::Formatter<'_>) {
+        for b in self.bitmap.iter() {
+            defmt::write!(f, "{}\n", b);
+        }
+    }
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> Frame<XSIZE, YSIZE, WORDS> {
+    /// Create an empty frame
+    pub const fn empty() -> Self {
+        Self {
+            bitmap: [Bitmap::empty(XSIZE); YSIZE],
+        }
+    }
+
+    /// Create a frame from a bitmap array
+    pub const fn new(bitmap: [Bitmap<WORDS>; YSIZE]) -> Self {
+        Self { bitmap }
+    }
+
+    /// Clear this frame (empty)
+    pub const fn clear(&mut self) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].clear_all();
+            y += 1;
+        }
+    }
+
+    /// Enable (x, y) on this frame
+    pub const fn set(&mut self, x: usize, y: usize) {
+        self.bitmap[y].set(x);
+    }
+
+    /// Disable (x, y) on this frame
+    pub const fn unset(&mut self, x: usize, y: usize) {
+        self.bitmap[y].clear(x);
+    }
+
+    /// Check if (x, y) is set on this frame
+    pub const fn is_set(&self, x: usize, y: usize) -> bool {
+        self.bitmap[y].is_set(x)
+    }
+
+    /// Logical OR with another frame
+    pub const fn or(&mut self, other: &Frame<XSIZE, YSIZE, WORDS>) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].or(&other.bitmap[y]);
+            y += 1;
+        }
+    }
+
+    /// Shift all rows left
+    pub const fn shift_left(&mut self, nbits: usize) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].shift_left(nbits);
+            y += 1;
+        }
+    }
+
+    /// Shift all rows right
+    pub const fn shift_right(&mut self, nbits: usize) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].shift_right(nbits);
+            y += 1;
+        }
+    }
+
+    /// Logical AND with another frame
+    pub const fn and(&mut self, other: &Frame<XSIZE, YSIZE, WORDS>) {
+        let mut y = 0;
+        while y < YSIZE {
+            self.bitmap[y].and(&other.bitmap[y]);
+            y += 1;
+        }
+    }
+
+    /// Rotate clockwise. Rotations by 90 and 270 degrees are meant for square frames, pixels
+    /// that don't fit a non-square frame are dropped.
+    pub fn rotate(&mut self, rotation: Rotation) {
+        self.remap(|x, y| match rotation {
+            Rotation::R0 => Some((x, y)),
+            Rotation::R90 => Some((y, (YSIZE - 1).checked_sub(x)?)),
+            Rotation::R180 => Some((XSIZE - 1 - x, YSIZE - 1 - y)),
+            Rotation::R270 => Some(((XSIZE - 1).checked_sub(y)?, x)),
+        });
+    }
+
+    /// Mirror left to right
+    pub fn mirror(&mut self) {
+        self.remap(|x, y| Some((XSIZE - 1 - x, y)));
+    }
+
+    /// Flip upside down
+    pub fn flip(&mut self) {
+        self.remap(|x, y| Some((x, YSIZE - 1 - y)));
+    }
+
+    /// Rebuild the frame, taking each pixel from the source coordinates returned by `source`
+    fn remap(&mut self, source: impl Fn(usize, usize) -> Option<(usize, usize)>) {
+        let original = *self;
+        self.clear();
+        for y in 0..YSIZE {
+            for x in 0..XSIZE {
+                if let Some((sx, sy)) = source(x, y) {
+                    if sx < XSIZE && sy < YSIZE && original.is_set(sx, sy) {
+                        self.set(x, y);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<const XSIZE: usize, const YSIZE: usize, const WORDS: usize> Default
+    for Frame<XSIZE, YSIZE, WORDS>
+{
+    fn default() -> Self {
+        Frame::empty()
+    }
+}
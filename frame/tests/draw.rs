@@ -0,0 +1,280 @@
+//! Drawing on frames, checked against ASCII art with `#` for a lit pixel
+use pomodoro_frame::{Bars, Frame};
+
+/// Frame drawn as ASCII art, one line per row
+fn art<const X: usize, const Y: usize, const W: usize>(frame: &Frame<X, Y, W>) -> String {
+    format!("{:?}", frame).replace('1', "#").replace('0', ".")
+}
+
+/// ASCII art without the indentation of the test source
+fn expected(art: &str) -> String {
+    art.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/// Frames can be drawn at compile time
+const BOX: Frame<5, 5> = {
+    let mut frame = Frame::empty();
+    frame.rect(0, 0, 5, 5);
+    frame.line(0, 0, 4, 4);
+    frame
+};
+
+#[test]
+fn lines_run_in_any_direction() {
+    assert_eq!(
+        art(&BOX),
+        expected(
+            "
+            #####
+            ##..#
+            #.#.#
+            #..##
+            #####
+            "
+        )
+    );
+
+    let mut frame: Frame<7, 4> = Frame::empty();
+    frame.line(0, 3, 6, 0);
+    frame.line(6, 3, 6, 3);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            .....##
+            ...##..
+            .##....
+            #.....#
+            "
+        )
+    );
+}
+
+/// Frames can be combined and shifted at compile time too
+const CROSS: Frame<5, 5> = {
+    let (mut down, mut up) = (Frame::empty(), Frame::empty());
+    down.line(0, 0, 4, 4);
+    up.line(0, 4, 4, 0);
+    down.or(&up);
+    down.shift_left(1);
+    down
+};
+
+#[test]
+fn frames_combine_at_compile_time() {
+    assert_eq!(
+        art(&CROSS),
+        expected(
+            "
+            ...#.
+            #.#..
+            .#...
+            #.#..
+            ...#.
+            "
+        )
+    );
+}
+
+#[test]
+fn shapes_are_clipped_to_the_frame() {
+    let mut frame: Frame<5, 5> = Frame::empty();
+    frame.rect(2, -1, 5, 3);
+    frame.fill_rect(-2, 3, 4, 9);
+    frame.line(-3, 4, 4, -3);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            .##..
+            #.###
+            .....
+            ##...
+            ##...
+            "
+        )
+    );
+
+    // Nothing to draw
+    let mut empty: Frame<5, 5> = Frame::empty();
+    empty.rect(1, 1, 0, 3);
+    empty.fill_rect(1, 1, 3, -1);
+    assert_eq!(empty, Frame::empty());
+}
+
+#[test]
+fn far_off_shapes_are_clipped_before_drawing() {
+    // These would take billions of steps pixel by pixel
+    let mut frame: Frame<5, 5> = Frame::empty();
+    frame.line(-1 << 40, 0, 1 << 40, 0);
+    frame.line(-1 << 40, (1 << 40) + 4, 4, 0);
+    frame.fill_rect(-1 << 40, 4, 1 << 41, 1 << 41);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            #####
+            ...#.
+            ..#..
+            .#...
+            #####
+            "
+        )
+    );
+
+    // Coordinates at the ends of the range don't overflow
+    let mut frame: Frame<5, 5> = Frame::empty();
+    frame.rect(1, 0, isize::MAX, 2);
+    frame.line(isize::MIN, 0, isize::MAX, 4);
+    // Through the origin, the only pixel of it on the frame
+    frame.line(isize::MAX, isize::MIN, isize::MIN, isize::MAX);
+    frame.fill_rect(isize::MIN, 4, isize::MAX, isize::MAX);
+    frame.copy(&BOX, isize::MAX, 0);
+    frame.copy(&BOX, isize::MIN, isize::MAX);
+    frame.rect(isize::MIN, isize::MIN, isize::MAX, isize::MAX);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            #####
+            .####
+            #####
+            .....
+            .....
+            "
+        )
+    );
+
+    let bars: Frame<3, 2> = Frame::bar_chart(&[usize::MAX, 1, 0, usize::MAX], Bars::Columns);
+    assert_eq!(
+        art(&bars),
+        expected(
+            "
+            #..
+            ##.
+            "
+        )
+    );
+}
+
+#[test]
+fn invert_and_xor_keep_the_width() {
+    // Rows of 12 don't fill their last byte
+    let mut frame: Frame<12, 2, 2> = Frame::empty();
+    frame.fill_rect(0, 0, 6, 1);
+    frame.invert();
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            ......######
+            ############
+            "
+        )
+    );
+
+    let mut other: Frame<12, 2, 2> = Frame::empty();
+    other.fill_rect(4, 0, 4, 2);
+    frame.xor(&other);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            ....##..####
+            ####....####
+            "
+        )
+    );
+    frame.xor(&other);
+    frame.invert();
+    let mut top = Frame::empty();
+    top.fill_rect(0, 0, 6, 1);
+    assert_eq!(frame, top);
+}
+
+#[test]
+fn rows_shift_up_and_down() {
+    let mut frame: Frame<3, 4> = Frame::empty();
+    frame.line(0, 0, 2, 0);
+    frame.plot(1, 1, true);
+    frame.shift_down(2);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            ...
+            ...
+            ###
+            .#.
+            "
+        )
+    );
+    frame.shift_up(3);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            .#.
+            ...
+            ...
+            ...
+            "
+        )
+    );
+    frame.shift_up(usize::MAX);
+    assert_eq!(frame, Frame::empty());
+}
+
+#[test]
+fn copies_replace_what_they_cover() {
+    let mut frame: Frame<5, 5> = Frame::empty();
+    frame.fill_rect(0, 0, 5, 5);
+    let mut dot: Frame<3, 3> = Frame::empty();
+    dot.plot(1, 1, true);
+    frame.copy(&dot, 3, -1);
+    frame.copy(&dot, -1, 2);
+    assert_eq!(
+        art(&frame),
+        expected(
+            "
+            ###.#
+            ###..
+            ..###
+            #.###
+            ..###
+            "
+        )
+    );
+}
+
+#[test]
+fn bar_charts_grow_from_the_edge() {
+    let columns: Frame<5, 4> = Frame::bar_chart(&[0, 1, 4, 9, 2, 3], Bars::Columns);
+    assert_eq!(
+        art(&columns),
+        expected(
+            "
+            ..##.
+            ..##.
+            ..###
+            .####
+            "
+        )
+    );
+
+    let rows: Frame<5, 3> = Frame::bar_chart(&[5, 0, 2], Bars::Rows);
+    assert_eq!(
+        art(&rows),
+        expected(
+            "
+            #####
+            .....
+            ##...
+            "
+        )
+    );
+}
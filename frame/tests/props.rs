@@ -0,0 +1,205 @@
+//! Property tests of bitmaps and frames against a model of one `bool` per pixel
+use pomodoro_frame::{Bitmap, Frame, GrayFrame, Rotation, GRAY_BITS, MAX_LEVEL};
+use proptest::prelude::*;
+
+/// A bitmap of four words, so bits carry over three word boundaries
+type Wide = Bitmap<4>;
+/// Width of a [`Wide`] bitmap
+const MAX_WIDTH: usize = Wide::CAPACITY;
+
+/// A bitmap and its pixels
+fn bitmap() -> impl Strategy<Value = (Wide, Vec<bool>)> {
+    (0..=MAX_WIDTH)
+        .prop_flat_map(|width| proptest::collection::vec(any::<bool>(), width))
+        .prop_map(|pixels| (build(&pixels), pixels))
+}
+
+fn build(pixels: &[bool]) -> Wide {
+    let mut bitmap = Wide::empty(pixels.len());
+    for (i, _) in pixels.iter().enumerate().filter(|(_, on)| **on) {
+        bitmap.set(i);
+    }
+    bitmap
+}
+
+fn pixels(bitmap: &Wide) -> Vec<bool> {
+    (0..bitmap.len()).map(|i| bitmap.is_set(i)).collect()
+}
+
+/// A 16x8 frame, as on a panel wider than a byte, and its pixels by row
+fn frame() -> impl Strategy<Value = Frame<16, 8, 2>> {
+    proptest::collection::vec(any::<bool>(), 16 * 8).prop_map(|pixels| {
+        let mut frame = Frame::empty();
+        for (i, _) in pixels.iter().enumerate().filter(|(_, on)| **on) {
+            frame.set(i % 16, i / 16);
+        }
+        frame
+    })
+}
+
+proptest! {
+    #[test]
+    fn set_and_clear_follow_the_model((mut bitmap, mut model) in bitmap(), bit in 0..MAX_WIDTH) {
+        prop_assert_eq!(pixels(&bitmap), model.clone());
+        if bit < model.len() {
+            bitmap.clear(bit);
+            model[bit] = false;
+            prop_assert_eq!(pixels(&bitmap), model.clone());
+            bitmap.set(bit);
+            model[bit] = true;
+            prop_assert_eq!(pixels(&bitmap), model);
+        }
+    }
+
+    #[test]
+    fn shift_left_carries_between_words((mut bitmap, model) in bitmap(), n in 0..MAX_WIDTH + 8) {
+        bitmap.shift_left(n);
+        let expected: Vec<bool> = (0..model.len())
+            .map(|i| model.get(i + n).copied().unwrap_or(false))
+            .collect();
+        prop_assert_eq!(pixels(&bitmap), expected);
+    }
+
+    #[test]
+    fn shift_right_carries_between_words((mut bitmap, model) in bitmap(), n in 0..MAX_WIDTH + 8) {
+        bitmap.shift_right(n);
+        let expected: Vec<bool> = (0..model.len())
+            .map(|i| i >= n && model[i - n])
+            .collect();
+        prop_assert_eq!(pixels(&bitmap), expected);
+    }
+
+    #[test]
+    fn shifted_out_bits_are_gone((mut bitmap, model) in bitmap(), n in 0..MAX_WIDTH) {
+        // Bits shifted past the width don't come back
+        bitmap.shift_right(n);
+        bitmap.shift_left(n);
+        let expected: Vec<bool> = (0..model.len())
+            .map(|i| i + n < model.len() && model[i])
+            .collect();
+        prop_assert_eq!(bitmap, build(&expected));
+    }
+
+    #[test]
+    fn or_and_follow_the_model((a, model) in bitmap(), seed in any::<u32>()) {
+        let other: Vec<bool> = (0..model.len()).map(|i| seed >> i & 1 == 1).collect();
+        let (mut or, mut and) = (a, a);
+        or.or(&build(&other));
+        and.and(&build(&other));
+        let zip = || model.iter().zip(&other);
+        prop_assert_eq!(pixels(&or), zip().map(|(a, b)| *a || *b).collect::<Vec<_>>());
+        prop_assert_eq!(pixels(&and), zip().map(|(a, b)| *a && *b).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn from_bits_matches_set(bits in any::<u32>(), width in 0..=MAX_WIDTH) {
+        let model: Vec<bool> = (0..width).map(|i| bits >> (width - 1 - i) & 1 == 1).collect();
+        prop_assert_eq!(Wide::from_bits(bits, width), build(&model));
+    }
+
+    #[test]
+    fn wide_frames_transform_back(frame in frame(), n in 0..16usize) {
+        let mut twice = frame;
+        twice.mirror();
+        twice.mirror();
+        prop_assert_eq!(twice, frame);
+        twice.flip();
+        twice.rotate(Rotation::R180);
+        twice.mirror();
+        prop_assert_eq!(twice, frame);
+
+        // Rows shift on their own
+        let mut shifted = frame;
+        shifted.shift_left(n);
+        for y in 0..8 {
+            for x in 0..16 {
+                let expected = x + n < 16 && frame.is_set(x + n, y);
+                prop_assert_eq!(shifted.is_set(x, y), expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn bitmaps_keep_their_width() {
+    assert_eq!(format!("{:?}", Wide::empty(20)), "0".repeat(20));
+    assert_eq!(format!("{:?}", Bitmap::<1>::new(0b10011, 5)), "10011");
+    assert_eq!(
+        format!("{:?}", Bitmap::<2>::from_bits(0x8001, 16)),
+        "1000000000000001"
+    );
+}
+
+#[test]
+#[should_panic]
+fn bitmaps_fit_their_words() {
+    Wide::empty(MAX_WIDTH + 1);
+}
+
+#[test]
+#[should_panic]
+fn frames_fit_their_words() {
+    Frame::<9, 1>::empty();
+}
+
+/// A 5x5 gray frame and its levels
+fn gray_frame() -> impl Strategy<Value = (GrayFrame<5, 5>, Vec<u8>)> {
+    proptest::collection::vec(0..=MAX_LEVEL, 25).prop_map(|levels| {
+        let mut frame = GrayFrame::empty();
+        for (i, level) in levels.iter().enumerate() {
+            frame.set(i % 5, i / 5, *level);
+        }
+        (frame, levels)
+    })
+}
+
+proptest! {
+    #[test]
+    fn gray_levels_are_split_into_bit_planes((frame, levels) in gray_frame()) {
+        for (i, level) in levels.iter().enumerate() {
+            let (x, y) = (i % 5, i / 5);
+            prop_assert_eq!(frame.level(x, y), *level);
+            for bit in 0..GRAY_BITS {
+                prop_assert_eq!(frame.plane(bit).is_set(x, y), level >> bit & 1 == 1);
+            }
+        }
+    }
+
+    #[test]
+    fn gray_frames_rotate_like_binary_frames((frame, _) in gray_frame(), level in 0..=MAX_LEVEL) {
+        let mut rotated = frame;
+        rotated.rotate(Rotation::R90);
+        for bit in 0..GRAY_BITS {
+            let mut plane = *frame.plane(bit);
+            plane.rotate(Rotation::R90);
+            prop_assert_eq!(*rotated.plane(bit), plane);
+        }
+
+        // Scaling never brightens, and keeps the frame at the top level
+        let mut scaled = frame;
+        scaled.scale(level);
+        for (x, y) in (0..25).map(|i| (i % 5, i / 5)) {
+            prop_assert!(scaled.level(x, y) <= frame.level(x, y));
+        }
+        let mut kept = frame;
+        kept.scale(MAX_LEVEL);
+        prop_assert_eq!(kept, frame);
+    }
+}
+
+#[test]
+fn binary_frames_are_fully_lit() {
+    let mut frame: Frame<3, 2> = Frame::empty();
+    frame.set(0, 0);
+    frame.set(2, 1);
+    let gray = GrayFrame::from(frame);
+    assert_eq!(format!("{:?}", gray), "700\n007\n");
+    assert_eq!(
+        format!("{:?}", GrayFrame::from_frame(&frame, 3)),
+        "300\n003\n"
+    );
+
+    let mut half = gray;
+    half.scale(4);
+    assert_eq!(format!("{:?}", half), "400\n004\n");
+}
@@ -0,0 +1,202 @@
+//! Melodies for the speaker
+//!
+//! A [`Melody`] is a tempo and a list of notes and rests. Note lengths are fractions of a whole
+//! note, as in sheet music, so the same melody can be played faster or slower. Ringtones in
+//! RTTTL format are parsed into melodies with [`rtttl`]. The crate is `no_std` and shared by
+//! the firmware and host tests.
+#![no_std]
+
+pub mod rtttl;
+
+use embassy_time::Duration;
+use Step::*;
+
+/// Work phases in a set. The rest after a set is a long rest.
+pub const SET_SIZE: u32 = 4;
+
+/// Notes of the chromatic scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Step {
+    C,
+    Cs,
+    D,
+    Ds,
+    E,
+    F,
+    Fs,
+    G,
+    Gs,
+    A,
+    As,
+    B,
+}
+
+/// Frequencies in Hz of C4 to B4
+const OCTAVE_4: [u16; 12] = [262, 277, 294, 311, 330, 349, 370, 392, 415, 440, 466, 494];
+
+/// Frequency in Hz of a note. Each octave doubles the frequency.
+pub const fn pitch(step: Step, octave: u8) -> u16 {
+    let freq = OCTAVE_4[step as usize];
+    if octave >= 4 {
+        freq << (octave - 4)
+    } else {
+        freq >> (4 - octave)
+    }
+}
+
+/// A note or a rest
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Note {
+    /// Frequency in Hz, `0` for a rest
+    pub freq: u16,
+    /// Length as a fraction of a whole note: 4 is a quarter note
+    pub length: u8,
+    /// A dotted note lasts half as long again
+    pub dotted: bool,
+}
+
+impl Note {
+    pub const fn new(step: Step, octave: u8, length: u8) -> Self {
+        Note {
+            freq: pitch(step, octave),
+            length,
+            dotted: false,
+        }
+    }
+
+    pub const fn rest(length: u8) -> Self {
+        Note {
+            freq: 0,
+            length,
+            dotted: false,
+        }
+    }
+
+    /// Makes the note half as long again
+    pub const fn dotted(self) -> Self {
+        Note {
+            dotted: true,
+            ..self
+        }
+    }
+
+    /// Whether this is a rest
+    pub fn is_rest(&self) -> bool {
+        self.freq == 0
+    }
+
+    /// Duration at a tempo in quarter notes per minute
+    pub fn duration(&self, tempo: u16) -> Duration {
+        // A whole note is four beats
+        let ms = 4 * 60_000 / (tempo.max(1) as u64 * self.length.max(1) as u64);
+        Duration::from_millis(if self.dotted { ms * 3 / 2 } else { ms })
+    }
+}
+
+/// A tune
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Melody<'a> {
+    /// Quarter notes per minute
+    pub tempo: u16,
+    pub notes: &'a [Note],
+}
+
+impl Melody<'_> {
+    /// Total duration of the melody
+    pub fn duration(&self) -> Duration {
+        self.notes
+            .iter()
+            .fold(Duration::from_ticks(0), |total, note| {
+                total + note.duration(self.tempo)
+            })
+    }
+}
+
+/// Rising arpeggio: back to work
+pub const WORK_START: Melody<'static> = Melody {
+    tempo: 160,
+    notes: &[
+        Note::new(C, 5, 8),
+        Note::new(E, 5, 8),
+        Note::new(G, 5, 8),
+        Note::new(C, 6, 4),
+    ],
+};
+
+/// Falling arpeggio: time for a break
+pub const REST_START: Melody<'static> = Melody {
+    tempo: 160,
+    notes: &[Note::new(G, 5, 8), Note::new(E, 5, 8), Note::new(C, 5, 4)],
+};
+
+/// Slow falling phrase: a set is done and a long break is due
+pub const LONG_REST: Melody<'static> = Melody {
+    tempo: 100,
+    notes: &[
+        Note::new(C, 6, 8),
+        Note::new(G, 5, 8),
+        Note::new(E, 5, 8),
+        Note::new(C, 5, 8),
+        Note::rest(8),
+        Note::new(D, 5, 8),
+        Note::new(C, 5, 2),
+    ],
+};
+
+/// Fanfare: the long break is over and a new set starts
+pub const COMPLETE: Melody<'static> = Melody {
+    tempo: 140,
+    notes: &[
+        Note::new(G, 5, 16),
+        Note::new(G, 5, 16),
+        Note::new(G, 5, 16),
+        Note::new(C, 6, 4).dotted(),
+        Note::new(A, 5, 8),
+        Note::new(C, 6, 4),
+    ],
+};
+
+/// Low buzz: a request was rejected
+pub const ERROR: Melody<'static> = Melody {
+    tempo: 120,
+    notes: &[Note::new(Fs, 3, 2)],
+};
+
+/// Click of the ticking clock
+pub const TICK: Melody<'static> = Melody {
+    tempo: 240,
+    notes: &[Note::new(C, 6, 32)],
+};
+
+/// Beeps of the alarm from level 1 to 3, higher and longer at each level
+pub const ALARM: [Melody<'static>; 3] = [
+    Melody {
+        tempo: 120,
+        notes: &[Note::new(A, 5, 16), Note::rest(16), Note::new(A, 5, 16)],
+    },
+    Melody {
+        tempo: 120,
+        notes: &[
+            Note::new(C, 6, 16),
+            Note::rest(16),
+            Note::new(C, 6, 16),
+            Note::rest(16),
+            Note::new(C, 6, 16),
+        ],
+    },
+    Melody {
+        tempo: 120,
+        notes: &[
+            Note::new(E, 6, 16),
+            Note::rest(16),
+            Note::new(E, 6, 16),
+            Note::rest(16),
+            Note::new(E, 6, 16),
+            Note::rest(16),
+            Note::new(E, 6, 16),
+        ],
+    },
+];
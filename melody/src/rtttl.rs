@@ -0,0 +1,260 @@
+//! RTTTL ringtone parser
+//!
+//! A Ring Tone Text Transfer Language ringtone has three sections separated by colons: a name,
+//! defaults, and comma-separated notes, e.g.
+//!
+//! ```text
+//! Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a
+//! ```
+//!
+//! The defaults set the note length (`d`), octave (`o`) and tempo in beats per minute (`b`).
+//! A note is an optional length, a note name from `a` to `g`, `p` for a rest, an optional `#`,
+//! an optional octave and an optional `.` for a dotted note.
+use crate::{pitch, Melody, Note, Step};
+use core::ops::RangeInclusive;
+
+/// Maximum number of notes in a ringtone
+pub const MAX_NOTES: usize = 64;
+/// Valid octaves
+const OCTAVES: RangeInclusive<u8> = 4..=7;
+/// Valid tempos in beats per minute
+const TEMPOS: RangeInclusive<u16> = 25..=900;
+
+/// What is wrong with a ringtone
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ErrorKind {
+    /// The name or defaults section isn't terminated by `:`
+    MissingSection,
+    /// A default other than `d`, `o` or `b`, or without a value
+    InvalidDefault,
+    /// A length other than 1, 2, 4, 8, 16 or 32
+    InvalidLength,
+    /// A note name other than `a` to `g`, `h` or `p`
+    InvalidNote,
+    /// An octave outside 4 to 7
+    InvalidOctave,
+    /// A tempo outside 25 to 900 beats per minute
+    InvalidTempo,
+    /// No notes
+    Empty,
+    /// More than [`MAX_NOTES`] notes
+    TooManyNotes,
+    /// More text than fits in flash
+    TooLong,
+}
+
+impl ErrorKind {
+    /// Short message reported to the BLE client
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::MissingSection => "ringtone needs name:defaults:notes",
+            Self::InvalidDefault => "ringtone defaults are d, o and b",
+            Self::InvalidLength => "note length must be 1, 2, 4, 8, 16 or 32",
+            Self::InvalidNote => "notes are a to g, or p for a rest",
+            Self::InvalidOctave => "octave must be between 4 and 7",
+            Self::InvalidTempo => "tempo must be between 25 and 900",
+            Self::Empty => "ringtone has no notes",
+            Self::TooManyNotes => "ringtone has too many notes",
+            Self::TooLong => "ringtone is too long",
+        }
+    }
+}
+
+/// A parse error and the byte offset where it was found
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub position: usize,
+}
+
+/// A parsed ringtone
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ringtone<'a> {
+    pub name: &'a str,
+    /// Beats (quarter notes) per minute
+    pub tempo: u16,
+    pub notes: heapless::Vec<Note, MAX_NOTES>,
+}
+
+impl Ringtone<'_> {
+    /// The ringtone as a melody
+    pub fn melody(&self) -> Melody<'_> {
+        Melody {
+            tempo: self.tempo,
+            notes: &self.notes,
+        }
+    }
+}
+
+/// Parses a ringtone
+pub fn parse(text: &str) -> Result<Ringtone<'_>, Error> {
+    let (name, rest) = split_section(text, 0)?;
+    let defaults_at = name.len() + 1;
+    let (defaults, notes) = split_section(rest, defaults_at)?;
+    let notes_at = defaults_at + defaults.len() + 1;
+
+    let mut length = 4;
+    let mut octave = 5;
+    let mut tempo = 63;
+    for (at, default) in fields(defaults, defaults_at) {
+        let (key, value) = default.split_once('=').ok_or(Error {
+            kind: ErrorKind::InvalidDefault,
+            position: at,
+        })?;
+        let value_at = at + key.len() + 1;
+        match key.trim() {
+            "d" => length = parse_length(value, value_at)?,
+            "o" => octave = parse_octave(value, value_at)?,
+            "b" => {
+                tempo = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|t| TEMPOS.contains(t))
+                    .ok_or(Error {
+                        kind: ErrorKind::InvalidTempo,
+                        position: value_at,
+                    })?
+            }
+            _ => {
+                return Err(Error {
+                    kind: ErrorKind::InvalidDefault,
+                    position: at,
+                })
+            }
+        }
+    }
+
+    let mut ringtone = Ringtone {
+        name: name.trim(),
+        tempo,
+        notes: heapless::Vec::new(),
+    };
+    for (at, note) in fields(notes, notes_at) {
+        let note = parse_note(note, at, length, octave)?;
+        ringtone.notes.push(note).map_err(|_| Error {
+            kind: ErrorKind::TooManyNotes,
+            position: at,
+        })?;
+    }
+    if ringtone.notes.is_empty() {
+        return Err(Error {
+            kind: ErrorKind::Empty,
+            position: notes_at,
+        });
+    }
+    Ok(ringtone)
+}
+
+/// Splits off a section terminated by `:`
+fn split_section(text: &str, at: usize) -> Result<(&str, &str), Error> {
+    text.split_once(':').ok_or(Error {
+        kind: ErrorKind::MissingSection,
+        position: at + text.len(),
+    })
+}
+
+/// Non-empty comma-separated fields with their offsets, without surrounding whitespace
+fn fields(section: &str, at: usize) -> impl Iterator<Item = (usize, &str)> {
+    section
+        .split(',')
+        .scan(at, |start, field| {
+            let field_at = *start;
+            *start += field.len() + 1;
+            let trimmed = field.trim_start();
+            Some((field_at + field.len() - trimmed.len(), trimmed.trim_end()))
+        })
+        .filter(|(_, field)| !field.is_empty())
+}
+
+fn parse_length(value: &str, at: usize) -> Result<u8, Error> {
+    match value.trim().parse() {
+        Ok(length @ (1 | 2 | 4 | 8 | 16 | 32)) => Ok(length),
+        _ => Err(Error {
+            kind: ErrorKind::InvalidLength,
+            position: at,
+        }),
+    }
+}
+
+fn parse_octave(value: &str, at: usize) -> Result<u8, Error> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|o| OCTAVES.contains(o))
+        .ok_or(Error {
+            kind: ErrorKind::InvalidOctave,
+            position: at,
+        })
+}
+
+/// Parses `[length]name[#][.][octave][.]`
+fn parse_note(note: &str, at: usize, length: u8, octave: u8) -> Result<Note, Error> {
+    let bytes = note.as_bytes();
+    let mut i = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    let length = match i {
+        0 => length,
+        _ => parse_length(&note[..i], at)?,
+    };
+
+    let invalid_note = Error {
+        kind: ErrorKind::InvalidNote,
+        position: at + i,
+    };
+    let name = bytes.get(i).ok_or(invalid_note)?.to_ascii_lowercase();
+    i += 1;
+    let sharp = bytes.get(i) == Some(&b'#');
+    if sharp {
+        i += 1;
+    }
+    let mut dotted = false;
+    if bytes.get(i) == Some(&b'.') {
+        dotted = true;
+        i += 1;
+    }
+    let octave = match bytes.get(i) {
+        Some(b) if b.is_ascii_digit() => {
+            i += 1;
+            parse_octave(&note[i - 1..i], at + i - 1)?
+        }
+        _ => octave,
+    };
+    if bytes.get(i) == Some(&b'.') {
+        dotted = true;
+        i += 1;
+    }
+    if i < bytes.len() {
+        return Err(Error {
+            kind: ErrorKind::InvalidNote,
+            position: at + i,
+        });
+    }
+
+    let step = match (name, sharp) {
+        (b'p', false) => {
+            let rest = Note::rest(length);
+            return Ok(if dotted { rest.dotted() } else { rest });
+        }
+        (b'c', false) => Step::C,
+        (b'c', true) => Step::Cs,
+        (b'd', false) => Step::D,
+        (b'd', true) => Step::Ds,
+        (b'e', false) => Step::E,
+        (b'f', false) => Step::F,
+        (b'f', true) => Step::Fs,
+        (b'g', false) => Step::G,
+        (b'g', true) => Step::Gs,
+        (b'a', false) => Step::A,
+        (b'a', true) => Step::As,
+        (b'b' | b'h', false) => Step::B,
+        _ => return Err(invalid_note),
+    };
+    Ok(Note {
+        freq: pitch(step, octave),
+        length,
+        dotted,
+    })
+}
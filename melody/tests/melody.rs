@@ -0,0 +1,35 @@
+use embassy_time::Duration;
+use pomodoro_melody::{self as melody, pitch, Melody, Note, Step};
+
+#[test]
+fn notes_have_pitch_and_duration() {
+    assert_eq!(pitch(Step::A, 4), 440);
+    assert_eq!(pitch(Step::A, 5), 880);
+    assert_eq!(pitch(Step::Fs, 3), 185);
+
+    let quarter = Note::new(Step::C, 5, 4);
+    assert_eq!(quarter.duration(120), Duration::from_millis(500));
+    assert_eq!(
+        Note::rest(8).dotted().duration(120),
+        Duration::from_millis(375)
+    );
+    assert!(Note::rest(8).is_rest() && !quarter.is_rest());
+}
+
+#[test]
+fn built_in_melodies_are_short_and_distinct() {
+    let melodies: [&Melody<'static>; 5] = [
+        &melody::WORK_START,
+        &melody::REST_START,
+        &melody::LONG_REST,
+        &melody::COMPLETE,
+        &melody::ERROR,
+    ];
+    for (i, a) in melodies.iter().enumerate() {
+        assert!(!a.notes.is_empty());
+        assert!(a.duration() <= Duration::from_secs(3));
+        for b in &melodies[i + 1..] {
+            assert!(a.notes != b.notes);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use pomodoro_melody::{rtttl, Note, Step};
+
+/// Well-known ringtones with their tempo, number of notes, and first and last note
+const RINGTONES: [(&str, u16, usize, Note, Note); 5] = [
+    (
+        "Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a",
+        225,
+        13,
+        Note::new(Step::E, 6, 8),
+        Note::new(Step::A, 5, 2),
+    ),
+    (
+        "Simpsons:d=4,o=5,b=160:c.6,e6,f#6,8a6,g.6,e6,c6,8a,8f#,8f#,8f#,2g,8p,8p,8f#,8f#,8f#,8g,\
+         a#.,8c6,8c6,8c6,c6",
+        160,
+        23,
+        Note::new(Step::C, 6, 4).dotted(),
+        Note::new(Step::C, 6, 4),
+    ),
+    (
+        "TakeOnMe:d=4,o=4,b=160:8f#5,8f#5,8f#5,8d5,8p,8b,8p,8e5,8p,8e5,8p,8e5,8g#5,8g#5,8a5,8b5,\
+         8a5,8a5,8a5,8e5,8p,8d5,8p,8f#5,8p,8f#5,8p,8f#5,8e5,8e5,8f#5,8e5",
+        160,
+        32,
+        Note::new(Step::Fs, 5, 8),
+        Note::new(Step::E, 5, 8),
+    ),
+    (
+        "Entertainer:d=4,o=5,b=140:8d,8d#,8e,c6,8e,c6,8e,2c.6,8c6,8d6,8d#6,8e6,8c6,8d6,e6,8b,d6,\
+         2c6,p,8d,8d#,8e,c6,8e,c6,8e,2c.6,8p,8a,8g,8f#,8a,8c6,e6,8d6,8c6,8a,2d6",
+        140,
+        38,
+        Note::new(Step::D, 5, 8),
+        Note::new(Step::D, 6, 2),
+    ),
+    (
+        "StarWars:d=4,o=5,b=45:32p,32f#,32f#,32f#,8b.,8f#.6,32e6,32d#6,32c#6,8b.6,16f#.6,32e6,\
+         32d#6,32c#6,8b.6,16f#.6,32e6,32d#6,32e6,8c#.6,32f#,32f#,32f#,8b.,8f#.6,32e6,32d#6,32c#6,\
+         8b.6,16f#.6,32e6,32d#6,32c#6,8b.6,16f#.6,32e6,32d#6,32e6,8c#6",
+        45,
+        39,
+        Note::rest(32),
+        Note::new(Step::Cs, 6, 8),
+    ),
+];
+
+#[test]
+fn rtttl_parses_well_known_ringtones() {
+    for (text, tempo, count, first, last) in RINGTONES {
+        let ringtone = rtttl::parse(text).unwrap();
+        assert_eq!(ringtone.tempo, tempo, "{text}");
+        assert_eq!(ringtone.notes.len(), count, "{text}");
+        assert_eq!(ringtone.notes.first(), Some(&first), "{text}");
+        assert_eq!(ringtone.notes.last(), Some(&last), "{text}");
+    }
+}
+
+#[test]
+fn rtttl_parses_ringtones() {
+    let nokia =
+        rtttl::parse("Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a").unwrap();
+    assert_eq!(nokia.name, "Nokia");
+    assert_eq!(nokia.tempo, 225);
+    assert_eq!(nokia.notes.len(), 13);
+    assert_eq!(nokia.notes[0], Note::new(Step::E, 6, 8));
+    assert_eq!(nokia.notes[12], Note::new(Step::A, 5, 2));
+    assert_eq!(nokia.melody().notes.len(), 13);
+
+    // Dots before or after the octave, pauses, whitespace and empty fields
+    let tone = rtttl::parse(" x : d=8 , o=6, b=120 : c.5 , 4d. ,p6., ").unwrap();
+    assert_eq!(tone.name, "x");
+    assert_eq!(tone.tempo, 120);
+    assert_eq!(
+        &tone.notes[..],
+        &[
+            Note::new(Step::C, 5, 8).dotted(),
+            Note::new(Step::D, 6, 4).dotted(),
+            Note::rest(8).dotted(),
+        ]
+    );
+}
+
+#[test]
+fn rtttl_reports_error_positions() {
+    let error = |text| rtttl::parse(text).map(|_| ()).unwrap_err();
+    let at = |kind, position| rtttl::Error { kind, position };
+    use rtttl::ErrorKind::*;
+
+    assert_eq!(error("tone"), at(MissingSection, 4));
+    assert_eq!(error("tone:d=4,o=5"), at(MissingSection, 12));
+    assert_eq!(error("tone:q=4:c"), at(InvalidDefault, 5));
+    assert_eq!(error("tone:d=3,o=5,b=100:c"), at(InvalidLength, 7));
+    assert_eq!(error("tone:d=4,o=9,b=100:c"), at(InvalidOctave, 11));
+    assert_eq!(error("tone:d=4,o=5,b=10:c"), at(InvalidTempo, 15));
+    assert_eq!(error("tone:d=4,o=5,b=100:c,9e"), at(InvalidLength, 21));
+    assert_eq!(error("tone:d=4,o=5,b=100:c, x"), at(InvalidNote, 22));
+    assert_eq!(error("tone:d=4,o=5,b=100:e#"), at(InvalidNote, 19));
+    assert_eq!(error("tone:d=4,o=5,b=100:c8"), at(InvalidOctave, 20));
+    assert_eq!(error("tone:d=4,o=5,b=100:"), at(Empty, 19));
+
+    let mut long: heapless::String<200> = heapless::String::from("long:d=4,o=5,b=100:");
+    for _ in 0..=rtttl::MAX_NOTES {
+        long.push_str("c,").unwrap();
+    }
+    assert_eq!(error(&long), at(TooManyNotes, 19 + 2 * rtttl::MAX_NOTES));
+}
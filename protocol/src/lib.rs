@@ -0,0 +1,282 @@
+//! Messages exchanged between the pomodoro firmware and a host over a serial port
+//!
+//! A host sends a [`Request`] and the device answers with a [`Response`]. Messages are
+//! serialized with postcard and COBS encoded. Each frame starts and ends with a `0x00`
+//! [`DELIMITER`], so frames can share the serial port with the text shell and the event
+//! stream: text never contains a `0x00` byte, and bytes outside of a frame are passed on
+//! by [`FrameReader`].
+#![no_std]
+
+use core::ops::RangeInclusive;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Frame delimiter
+pub const DELIMITER: u8 = 0x00;
+/// Maximum size of an encoded frame, including both delimiters
+pub const MAX_FRAME_SIZE: usize = 32;
+
+/// Valid work times in minutes
+pub const WORK_MINUTES: RangeInclusive<u32> = 1..=120;
+/// Valid rest times in minutes
+pub const REST_MINUTES: RangeInclusive<u32> = 1..=60;
+/// Highest display brightness level. The lowest is `0`.
+pub const MAX_BRIGHTNESS: u8 = 10;
+/// Longest time between ticks during work in seconds
+pub const MAX_TICK_INTERVAL: u8 = 60;
+
+/// Timer config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Work time in minutes
+    pub work_minutes: u32,
+    /// Rest time in minutes
+    pub rest_minutes: u32,
+}
+
+impl Config {
+    /// Checks that both times are in range
+    pub fn validate(&self) -> Result<(), Error> {
+        if !WORK_MINUTES.contains(&self.work_minutes) {
+            return Err(Error::WorkOutOfRange);
+        }
+        if !REST_MINUTES.contains(&self.rest_minutes) {
+            return Err(Error::RestOutOfRange);
+        }
+        Ok(())
+    }
+}
+
+/// Timer state
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    Paused,
+    Running,
+    Resting,
+}
+
+/// Current state and remaining time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    pub state: State,
+    /// Remaining time of the current phase in seconds
+    pub remaining: u32,
+}
+
+/// Completed phases since boot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// Completed work phases
+    pub work_completed: u32,
+    /// Completed rest phases
+    pub rest_completed: u32,
+    /// Time spent in work phases in seconds
+    pub focus_time: u32,
+}
+
+/// Timer controls
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    /// Start or resume a work phase
+    Start,
+    /// Pause a work phase
+    Pause,
+    /// Skip to the next phase
+    Skip,
+}
+
+/// Message from the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Request {
+    GetStatus,
+    GetStats,
+    GetConfig,
+    /// Save a timer config. It applies from the next phase.
+    SetConfig(Config),
+    Command(Command),
+}
+
+/// Message from the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    /// The request has been accepted
+    Ok,
+    Status(Status),
+    Stats(Stats),
+    Config(Config),
+    Error(Error),
+}
+
+/// Errors reported by the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The request could not be decoded
+    Malformed,
+    /// The device can't take the request right now
+    Busy,
+    WorkOutOfRange,
+    RestOutOfRange,
+    /// The config could not be saved
+    Storage,
+}
+
+impl Error {
+    /// Short description of the error
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Malformed => "malformed request",
+            Self::Busy => "device is busy, try again",
+            Self::WorkOutOfRange => "work time must be between 1 and 120 minutes",
+            Self::RestOutOfRange => "rest time must be between 1 and 60 minutes",
+            Self::Storage => "failed to save config to flash",
+        }
+    }
+}
+
+/// Device side of the protocol
+pub trait Device {
+    fn status(&self) -> Status;
+    fn stats(&self) -> Stats;
+    fn config(&self) -> Config;
+    /// Saves a config that has already been validated
+    fn set_config(&mut self, config: Config) -> Result<(), Error>;
+    fn command(&mut self, command: Command) -> Result<(), Error>;
+}
+
+impl Request {
+    /// Runs the request on a device
+    pub fn handle<D: Device>(self, device: &mut D) -> Response {
+        let result = match self {
+            Request::GetStatus => return Response::Status(device.status()),
+            Request::GetStats => return Response::Stats(device.stats()),
+            Request::GetConfig => return Response::Config(device.config()),
+            Request::SetConfig(config) => config.validate().and_then(|_| device.set_config(config)),
+            Request::Command(command) => device.command(command),
+        };
+        match result {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        }
+    }
+}
+
+/// Encodes a message as a frame, including both delimiters
+pub fn encode<'a, T: Serialize>(
+    message: &T,
+    buf: &'a mut [u8],
+) -> Result<&'a mut [u8], postcard::Error> {
+    let (first, rest) = buf
+        .split_first_mut()
+        .ok_or(postcard::Error::SerializeBufferFull)?;
+    *first = DELIMITER;
+    let len = postcard::to_slice_cobs(message, rest)?.len();
+    Ok(&mut buf[..=len])
+}
+
+/// Decodes a frame. Delimiters are optional.
+pub fn decode<T: DeserializeOwned>(frame: &mut [u8]) -> Result<T, postcard::Error> {
+    let start = frame.iter().take_while(|b| **b == DELIMITER).count();
+    postcard::from_bytes_cobs(&mut frame[start..])
+}
+
+/// Decodes a request frame, runs it on a device and encodes the response into `buf`
+pub fn respond<'a, D: Device>(frame: &mut [u8], device: &mut D, buf: &'a mut [u8]) -> &'a [u8] {
+    let response = match decode::<Request>(frame) {
+        Ok(request) => request.handle(device),
+        Err(_) => Response::Error(Error::Malformed),
+    };
+    // All responses fit in a frame
+    match encode(&response, buf) {
+        Ok(frame) => frame,
+        Err(_) => &[],
+    }
+}
+
+/// Result of feeding a byte to the [`FrameReader`]
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Input<'a> {
+    /// A byte outside of a frame, e.g. shell input
+    Byte(u8),
+    /// The byte is part of a frame
+    Pending,
+    /// A frame is complete. It still has to be decoded.
+    Frame(&'a mut [u8]),
+}
+
+/// Where the [`FrameReader`] is in the byte stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Outside of a frame
+    Text,
+    /// Inside a frame
+    Frame,
+    /// Inside a frame that doesn't fit
+    Discard,
+}
+
+/// Splits a byte stream into frames and bytes outside of frames
+pub struct FrameReader<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    mode: Mode,
+}
+
+impl<const N: usize> Default for FrameReader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FrameReader<N> {
+    /// Creates a reader outside of a frame
+    pub const fn new() -> Self {
+        FrameReader {
+            buf: [0; N],
+            len: 0,
+            mode: Mode::Text,
+        }
+    }
+
+    /// Feeds a received byte
+    pub fn push(&mut self, byte: u8) -> Input<'_> {
+        match (byte, self.mode) {
+            (DELIMITER, Mode::Frame) if self.len > 0 => {
+                // Keep the delimiter as postcard expects a terminated frame
+                let len = self.len;
+                self.buf[len] = DELIMITER;
+                self.len = 0;
+                self.mode = Mode::Text;
+                Input::Frame(&mut self.buf[..=len])
+            }
+            (DELIMITER, Mode::Discard) => {
+                self.mode = Mode::Text;
+                Input::Pending
+            }
+            (DELIMITER, _) => {
+                self.mode = Mode::Frame;
+                Input::Pending
+            }
+            (_, Mode::Frame) => {
+                // Leave room for the delimiter
+                if self.len + 1 < N {
+                    self.buf[self.len] = byte;
+                    self.len += 1;
+                } else {
+                    self.len = 0;
+                    self.mode = Mode::Discard;
+                }
+                Input::Pending
+            }
+            (_, Mode::Discard) => Input::Pending,
+            (_, Mode::Text) => Input::Byte(byte),
+        }
+    }
+}
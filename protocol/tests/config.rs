@@ -0,0 +1,45 @@
+//! Config validation and the messages of its errors
+use pomodoro_protocol::{Config, Error, REST_MINUTES, WORK_MINUTES};
+
+#[test]
+fn configs_are_checked_against_the_ranges() {
+    for work in [*WORK_MINUTES.start(), *WORK_MINUTES.end()] {
+        for rest in [*REST_MINUTES.start(), *REST_MINUTES.end()] {
+            let config = Config {
+                work_minutes: work,
+                rest_minutes: rest,
+            };
+            assert_eq!(config.validate(), Ok(()));
+        }
+    }
+
+    let config = Config {
+        work_minutes: WORK_MINUTES.end() + 1,
+        rest_minutes: *REST_MINUTES.start(),
+    };
+    assert_eq!(config.validate(), Err(Error::WorkOutOfRange));
+    let config = Config {
+        work_minutes: *WORK_MINUTES.start(),
+        rest_minutes: REST_MINUTES.start() - 1,
+    };
+    assert_eq!(config.validate(), Err(Error::RestOutOfRange));
+}
+
+#[test]
+fn range_errors_name_the_ranges() {
+    let expected = |what, range: std::ops::RangeInclusive<u32>| {
+        format!(
+            "{what} time must be between {} and {} minutes",
+            range.start(),
+            range.end()
+        )
+    };
+    assert_eq!(
+        Error::WorkOutOfRange.message(),
+        expected("work", WORK_MINUTES)
+    );
+    assert_eq!(
+        Error::RestOutOfRange.message(),
+        expected("rest", REST_MINUTES)
+    );
+}
@@ -0,0 +1,248 @@
+//! Framing of requests and responses, and the request handler
+use pomodoro_protocol::{
+    decode, encode, respond, Command, Config, Device, Error, FrameReader, Input, Request, Response,
+    State, Stats, Status, DELIMITER, MAX_FRAME_SIZE,
+};
+
+/// Device that records what it was asked to do
+#[derive(Default)]
+struct Recorder {
+    config: Option<Config>,
+    commands: Vec<Command>,
+    /// Error returned when saving a config
+    save_error: Option<Error>,
+}
+
+impl Device for Recorder {
+    fn status(&self) -> Status {
+        Status {
+            state: State::Resting,
+            remaining: 299,
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            work_completed: 4,
+            rest_completed: 3,
+            focus_time: 6000,
+        }
+    }
+
+    fn config(&self) -> Config {
+        self.config.unwrap_or(Config {
+            work_minutes: 25,
+            rest_minutes: 5,
+        })
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        if let Some(e) = self.save_error {
+            return Err(e);
+        }
+        self.config = Some(config);
+        Ok(())
+    }
+
+    fn command(&mut self, command: Command) -> Result<(), Error> {
+        self.commands.push(command);
+        Ok(())
+    }
+}
+
+/// Feeds bytes to a reader and collects the text bytes and the frames
+fn read<const N: usize>(reader: &mut FrameReader<N>, bytes: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let (mut text, mut frames) = (Vec::new(), Vec::new());
+    for b in bytes {
+        match reader.push(*b) {
+            Input::Byte(b) => text.push(b),
+            Input::Pending => (),
+            Input::Frame(frame) => frames.push(frame.to_vec()),
+        }
+    }
+    (text, frames)
+}
+
+/// Runs an encoded request through the handler and decodes the response
+fn ask(device: &mut Recorder, request: &Request) -> Response {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let mut frame = encode(request, &mut buf).unwrap().to_vec();
+    let mut out = [0u8; MAX_FRAME_SIZE];
+    let mut response = respond(&mut frame, device, &mut out).to_vec();
+    decode(&mut response).unwrap()
+}
+
+const CONFIG: Config = Config {
+    work_minutes: 50,
+    rest_minutes: 10,
+};
+
+#[test]
+fn messages_round_trip_through_frames() {
+    let requests = [
+        Request::GetStatus,
+        Request::GetStats,
+        Request::GetConfig,
+        Request::SetConfig(CONFIG),
+        Request::Command(Command::Skip),
+    ];
+    for request in requests {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let frame = encode(&request, &mut buf).unwrap();
+        // Delimited on both ends and free of delimiters in between
+        assert_eq!(frame.first(), Some(&DELIMITER));
+        assert_eq!(frame.last(), Some(&DELIMITER));
+        assert!(!frame[1..frame.len() - 1].contains(&DELIMITER));
+        assert_eq!(decode::<Request>(frame).unwrap(), request);
+    }
+
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let response = Response::Status(Status {
+        state: State::Running,
+        remaining: u32::MAX,
+    });
+    let frame = encode(&response, &mut buf).unwrap();
+    assert!(frame.len() <= MAX_FRAME_SIZE);
+    assert_eq!(decode::<Response>(frame).unwrap(), response);
+}
+
+#[test]
+fn encode_reports_a_full_buffer() {
+    let mut buf = [0u8; 2];
+    assert!(encode(&Request::SetConfig(CONFIG), &mut buf).is_err());
+    assert!(encode(&Request::GetStatus, &mut []).is_err());
+}
+
+#[test]
+fn reader_splits_frames_from_text() {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let frame = encode(&Request::SetConfig(CONFIG), &mut buf)
+        .unwrap()
+        .to_vec();
+
+    let mut stream = b"status\r".to_vec();
+    stream.extend_from_slice(&frame);
+    stream.extend_from_slice(b"help\r");
+    let mut reader: FrameReader<MAX_FRAME_SIZE> = FrameReader::new();
+    let (text, mut frames) = read(&mut reader, &stream);
+    assert_eq!(text, b"status\rhelp\r");
+    assert_eq!(frames.len(), 1);
+    assert_eq!(
+        decode::<Request>(&mut frames[0]).unwrap(),
+        Request::SetConfig(CONFIG)
+    );
+}
+
+#[test]
+fn reader_joins_frames_split_across_reads() {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let frame = encode(&Request::Command(Command::Pause), &mut buf)
+        .unwrap()
+        .to_vec();
+    let (head, tail) = frame.split_at(frame.len() / 2);
+
+    let mut reader: FrameReader<MAX_FRAME_SIZE> = FrameReader::new();
+    let (text, frames) = read(&mut reader, head);
+    assert!(text.is_empty() && frames.is_empty());
+    let (text, mut frames) = read(&mut reader, tail);
+    assert!(text.is_empty());
+    assert_eq!(
+        decode::<Request>(&mut frames[0]).unwrap(),
+        Request::Command(Command::Pause)
+    );
+
+    // Back to back frames
+    let stream = [frame.as_slice(), frame.as_slice()].concat();
+    let (_, frames) = read(&mut reader, &stream);
+    assert_eq!(frames.len(), 2);
+}
+
+#[test]
+fn reader_drops_oversize_frames_and_recovers() {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let frame = encode(&Request::GetStats, &mut buf).unwrap().to_vec();
+
+    let mut stream = vec![DELIMITER];
+    stream.extend_from_slice(&[0x55; 3 * MAX_FRAME_SIZE]);
+    stream.push(DELIMITER);
+    stream.extend_from_slice(b"ok");
+    stream.extend_from_slice(&frame);
+    let mut reader: FrameReader<MAX_FRAME_SIZE> = FrameReader::new();
+    let (text, mut frames) = read(&mut reader, &stream);
+    assert_eq!(text, b"ok");
+    assert_eq!(frames.len(), 1);
+    assert_eq!(
+        decode::<Request>(&mut frames[0]).unwrap(),
+        Request::GetStats
+    );
+}
+
+#[test]
+fn device_answers_requests() {
+    let mut device = Recorder::default();
+    assert_eq!(
+        ask(&mut device, &Request::GetStatus),
+        Response::Status(device.status())
+    );
+    assert_eq!(
+        ask(&mut device, &Request::GetStats),
+        Response::Stats(device.stats())
+    );
+    assert_eq!(ask(&mut device, &Request::SetConfig(CONFIG)), Response::Ok);
+    assert_eq!(
+        ask(&mut device, &Request::GetConfig),
+        Response::Config(CONFIG)
+    );
+    assert_eq!(
+        ask(&mut device, &Request::Command(Command::Start)),
+        Response::Ok
+    );
+    assert_eq!(device.commands, [Command::Start]);
+}
+
+#[test]
+fn device_reports_errors() {
+    let mut device = Recorder::default();
+    let long_work = Config {
+        work_minutes: 121,
+        rest_minutes: 5,
+    };
+    assert_eq!(
+        ask(&mut device, &Request::SetConfig(long_work)),
+        Response::Error(Error::WorkOutOfRange)
+    );
+    let no_rest = Config {
+        work_minutes: 25,
+        rest_minutes: 0,
+    };
+    assert_eq!(
+        ask(&mut device, &Request::SetConfig(no_rest)),
+        Response::Error(Error::RestOutOfRange)
+    );
+    assert_eq!(device.config, None);
+
+    device.save_error = Some(Error::Storage);
+    assert_eq!(
+        ask(&mut device, &Request::SetConfig(CONFIG)),
+        Response::Error(Error::Storage)
+    );
+}
+
+#[test]
+fn garbage_is_malformed() {
+    let mut device = Recorder::default();
+    let mut out = [0u8; MAX_FRAME_SIZE];
+    for garbage in [
+        &[0x03, 0xFF, 0xFF, 0x00][..],
+        &[0x00, 0x00],
+        &[0x01, 0x09, 0x00],
+    ] {
+        let mut frame = garbage.to_vec();
+        let mut response = respond(&mut frame, &mut device, &mut out).to_vec();
+        assert_eq!(
+            decode::<Response>(&mut response).unwrap(),
+            Response::Error(Error::Malformed)
+        );
+    }
+    assert!(device.commands.is_empty());
+}
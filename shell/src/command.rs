@@ -0,0 +1,143 @@
+//! Shell commands
+use pomodoro_protocol::{self as protocol, Config, MAX_BRIGHTNESS, MAX_TICK_INTERVAL};
+
+/// Usage shown by `help`
+pub const HELP: &str = "\
+status                show state and remaining time\r
+start                 start or resume a work phase\r
+pause                 pause a work phase\r
+skip                  skip to the next phase\r
+config                show the timer config\r
+config <work> <rest>  save a timer config in minutes\r
+stats                 show session and advertising stats\r
+erase                 erase the timer config from flash\r
+brightness <0-10>     set the display brightness\r
+brightness auto       follow the ambient light\r
+alarm on|off          ring at the end of a phase until a button is pressed\r
+tick off|<1-60>       tick every few seconds during work\r
+group <0-255>         follow only leaders of the same group\r
+help                  show this help\r
+";
+
+/// A shell command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    Help,
+    Status,
+    Start,
+    Pause,
+    Skip,
+    GetConfig,
+    /// A config that is in range
+    SetConfig(Config),
+    Stats,
+    Erase,
+    /// Brightness level up to [`MAX_BRIGHTNESS`]
+    Brightness(u8),
+    AutoBrightness,
+    Alarm(bool),
+    /// Seconds between ticks up to [`MAX_TICK_INTERVAL`], `0` for no ticks
+    Tick(u8),
+    /// Group id of group mode
+    Group(u8),
+}
+
+/// Errors from parsing a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShellError {
+    UnknownCommand,
+    MissingArgument,
+    TooManyArguments,
+    InvalidArgument,
+    /// A config out of range
+    Config(protocol::Error),
+}
+
+impl ShellError {
+    /// Message shown to the user
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::UnknownCommand => "unknown command, try `help`",
+            Self::MissingArgument => "missing argument",
+            Self::TooManyArguments => "too many arguments",
+            Self::InvalidArgument => "invalid argument",
+            Self::Config(e) => e.message(),
+        }
+    }
+}
+
+impl From<protocol::Error> for ShellError {
+    fn from(e: protocol::Error) -> Self {
+        ShellError::Config(e)
+    }
+}
+
+impl From<core::num::ParseIntError> for ShellError {
+    fn from(_: core::num::ParseIntError) -> Self {
+        ShellError::InvalidArgument
+    }
+}
+
+impl Command {
+    /// Parses a command line
+    pub fn parse(line: &str) -> Result<Command, ShellError> {
+        let mut args = line.split_ascii_whitespace();
+        let name = args.next().ok_or(ShellError::UnknownCommand)?;
+
+        let command = match name {
+            "help" | "?" => Command::Help,
+            "status" => Command::Status,
+            "start" => Command::Start,
+            "pause" => Command::Pause,
+            "skip" => Command::Skip,
+            "stats" => Command::Stats,
+            "erase" => Command::Erase,
+            "config" => match args.next() {
+                None => Command::GetConfig,
+                Some(work) => {
+                    let rest = args.next().ok_or(ShellError::MissingArgument)?;
+                    let config = Config {
+                        work_minutes: work.parse()?,
+                        rest_minutes: rest.parse()?,
+                    };
+                    config.validate()?;
+                    Command::SetConfig(config)
+                }
+            },
+            "brightness" => match args.next().ok_or(ShellError::MissingArgument)? {
+                "auto" => Command::AutoBrightness,
+                level => {
+                    let level: u8 = level.parse()?;
+                    if level > MAX_BRIGHTNESS {
+                        return Err(ShellError::InvalidArgument);
+                    }
+                    Command::Brightness(level)
+                }
+            },
+            "alarm" => match args.next().ok_or(ShellError::MissingArgument)? {
+                "on" => Command::Alarm(true),
+                "off" => Command::Alarm(false),
+                _ => return Err(ShellError::InvalidArgument),
+            },
+            "tick" => match args.next().ok_or(ShellError::MissingArgument)? {
+                "off" => Command::Tick(0),
+                interval => {
+                    let interval: u8 = interval.parse()?;
+                    if interval == 0 || interval > MAX_TICK_INTERVAL {
+                        return Err(ShellError::InvalidArgument);
+                    }
+                    Command::Tick(interval)
+                }
+            },
+            "group" => Command::Group(args.next().ok_or(ShellError::MissingArgument)?.parse()?),
+            _ => return Err(ShellError::UnknownCommand),
+        };
+
+        if args.next().is_some() {
+            return Err(ShellError::TooManyArguments);
+        }
+        Ok(command)
+    }
+}
@@ -0,0 +1,90 @@
+//! Line editing for the serial shell
+
+/// Result of feeding a byte to the [`LineEditor`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edit {
+    /// Nothing to do
+    None,
+    /// A character was added and should be echoed
+    Echo(u8),
+    /// The last `n` characters were erased
+    Erase(usize),
+    /// A line is complete and can be read with [`LineEditor::line`]
+    Line,
+}
+
+/// Line editor supporting backspace, `Ctrl-U` (erase line) and `Ctrl-C` (cancel line)
+pub struct LineEditor<const N: usize> {
+    buf: heapless::String<N>,
+    complete: bool,
+}
+
+impl<const N: usize> Default for LineEditor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LineEditor<N> {
+    const BACKSPACE: u8 = 0x08;
+    const DELETE: u8 = 0x7F;
+    const CTRL_C: u8 = 0x03;
+    const CTRL_U: u8 = 0x15;
+
+    /// Creates an empty line editor
+    pub const fn new() -> Self {
+        LineEditor {
+            buf: heapless::String::new(),
+            complete: false,
+        }
+    }
+
+    /// Feeds a byte received from the terminal
+    pub fn push(&mut self, byte: u8) -> Edit {
+        // Start a new line after the previous one has been read
+        if self.complete {
+            self.buf.clear();
+            self.complete = false;
+        }
+
+        match byte {
+            b'\r' | b'\n' => {
+                // Ignore the second half of "\r\n" and empty lines
+                if self.buf.is_empty() {
+                    Edit::None
+                } else {
+                    self.complete = true;
+                    Edit::Line
+                }
+            }
+            Self::BACKSPACE | Self::DELETE => match self.buf.pop() {
+                Some(_) => Edit::Erase(1),
+                None => Edit::None,
+            },
+            Self::CTRL_C | Self::CTRL_U => {
+                let n = self.buf.len();
+                self.buf.clear();
+                if n > 0 {
+                    Edit::Erase(n)
+                } else {
+                    Edit::None
+                }
+            }
+            0x20..=0x7E => match self.buf.push(byte as char) {
+                Ok(()) => Edit::Echo(byte),
+                Err(()) => Edit::None,
+            },
+            _ => Edit::None,
+        }
+    }
+
+    /// Returns the line once [`Edit::Line`] has been returned
+    pub fn line(&self) -> &str {
+        if self.complete {
+            self.buf.as_str()
+        } else {
+            ""
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! Command line parsing and editing for the serial shell
+//!
+//! A [`LineEditor`] turns bytes from a terminal into lines, and [`Command::parse`] turns a line
+//! into a [`Command`]. The crate is `no_std` and shared by the firmware and host tests.
+#![no_std]
+
+mod command;
+mod editor;
+pub use command::*;
+pub use editor::*;
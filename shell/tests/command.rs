@@ -0,0 +1,101 @@
+//! Parsing command lines
+use pomodoro_protocol::{self as protocol, Config, MAX_BRIGHTNESS, MAX_TICK_INTERVAL};
+use pomodoro_shell::{Command, ShellError, HELP};
+
+#[test]
+fn commands_parse() {
+    assert_eq!(Command::parse("status"), Ok(Command::Status));
+    assert_eq!(Command::parse("  skip "), Ok(Command::Skip));
+    assert_eq!(Command::parse("?"), Ok(Command::Help));
+    assert_eq!(Command::parse("config"), Ok(Command::GetConfig));
+    assert_eq!(
+        Command::parse("config 50 10"),
+        Ok(Command::SetConfig(Config {
+            work_minutes: 50,
+            rest_minutes: 10,
+        }))
+    );
+    assert_eq!(
+        Command::parse("brightness auto"),
+        Ok(Command::AutoBrightness)
+    );
+    assert_eq!(Command::parse("brightness 0"), Ok(Command::Brightness(0)));
+    assert_eq!(Command::parse("alarm on"), Ok(Command::Alarm(true)));
+    assert_eq!(Command::parse("alarm off"), Ok(Command::Alarm(false)));
+    assert_eq!(Command::parse("tick 5"), Ok(Command::Tick(5)));
+    assert_eq!(Command::parse("tick off"), Ok(Command::Tick(0)));
+    assert_eq!(Command::parse("group 7"), Ok(Command::Group(7)));
+}
+
+#[test]
+fn bad_lines_are_rejected() {
+    assert_eq!(Command::parse(""), Err(ShellError::UnknownCommand));
+    assert_eq!(Command::parse("reboot"), Err(ShellError::UnknownCommand));
+    assert_eq!(
+        Command::parse("pause now"),
+        Err(ShellError::TooManyArguments)
+    );
+    assert_eq!(
+        Command::parse("config 50"),
+        Err(ShellError::MissingArgument)
+    );
+    assert_eq!(
+        Command::parse("config 50 ten"),
+        Err(ShellError::InvalidArgument)
+    );
+    assert_eq!(
+        Command::parse("config -5 10"),
+        Err(ShellError::InvalidArgument)
+    );
+    assert_eq!(
+        Command::parse("config 25 90"),
+        Err(ShellError::Config(protocol::Error::RestOutOfRange))
+    );
+    assert_eq!(
+        Command::parse("alarm loud"),
+        Err(ShellError::InvalidArgument)
+    );
+    assert_eq!(Command::parse("tick"), Err(ShellError::MissingArgument));
+    assert_eq!(Command::parse("tick 0"), Err(ShellError::InvalidArgument));
+    assert_eq!(
+        Command::parse("group 256"),
+        Err(ShellError::InvalidArgument)
+    );
+}
+
+#[test]
+fn limits_are_inclusive() {
+    let brightness = format!("brightness {MAX_BRIGHTNESS}");
+    assert_eq!(
+        Command::parse(&brightness),
+        Ok(Command::Brightness(MAX_BRIGHTNESS))
+    );
+    let brightness = format!("brightness {}", MAX_BRIGHTNESS + 1);
+    assert_eq!(
+        Command::parse(&brightness),
+        Err(ShellError::InvalidArgument)
+    );
+
+    let tick = format!("tick {MAX_TICK_INTERVAL}");
+    assert_eq!(Command::parse(&tick), Ok(Command::Tick(MAX_TICK_INTERVAL)));
+    let tick = format!("tick {}", MAX_TICK_INTERVAL + 1);
+    assert_eq!(Command::parse(&tick), Err(ShellError::InvalidArgument));
+}
+
+#[test]
+fn help_shows_the_limits() {
+    assert!(HELP.contains(&format!("brightness <0-{MAX_BRIGHTNESS}>")));
+    assert!(HELP.contains(&format!("tick off|<1-{MAX_TICK_INTERVAL}>")));
+}
+
+#[test]
+fn errors_have_messages() {
+    assert_eq!(
+        ShellError::Config(protocol::Error::WorkOutOfRange).message(),
+        protocol::Error::WorkOutOfRange.message()
+    );
+    assert_eq!(
+        ShellError::UnknownCommand.message(),
+        "unknown command, try `help`"
+    );
+}
@@ -0,0 +1,56 @@
+//! Editing lines from a terminal
+use pomodoro_shell::{Edit, LineEditor};
+
+/// Feeds bytes to an editor and returns the last edit
+fn type_in<const N: usize>(editor: &mut LineEditor<N>, bytes: &[u8]) -> Edit {
+    bytes.iter().fold(Edit::None, |_, b| editor.push(*b))
+}
+
+#[test]
+fn lines_are_echoed_and_completed() {
+    let mut editor: LineEditor<16> = LineEditor::new();
+    assert_eq!(editor.push(b's'), Edit::Echo(b's'));
+    assert_eq!(editor.line(), "");
+    type_in(&mut editor, b"tatus");
+    assert_eq!(editor.push(b'\r'), Edit::Line);
+    assert_eq!(editor.line(), "status");
+
+    // The "\n" of "\r\n" and empty lines are ignored
+    assert_eq!(editor.push(b'\n'), Edit::None);
+    assert_eq!(editor.push(b'\r'), Edit::None);
+
+    // A new line starts after the last one has been read
+    type_in(&mut editor, b"skip");
+    assert_eq!(editor.push(b'\n'), Edit::Line);
+    assert_eq!(editor.line(), "skip");
+}
+
+#[test]
+fn characters_and_lines_are_erased() {
+    let mut editor: LineEditor<16> = LineEditor::new();
+    type_in(&mut editor, b"stax");
+    assert_eq!(editor.push(0x7F), Edit::Erase(1));
+    assert_eq!(editor.push(0x08), Edit::Erase(1));
+    type_in(&mut editor, b"art");
+    assert_eq!(editor.push(b'\r'), Edit::Line);
+    assert_eq!(editor.line(), "start");
+
+    type_in(&mut editor, b"xyz");
+    assert_eq!(editor.push(0x15), Edit::Erase(3));
+    assert_eq!(editor.push(0x7F), Edit::None);
+    type_in(&mut editor, b"ab");
+    assert_eq!(editor.push(0x03), Edit::Erase(2));
+    assert_eq!(editor.push(0x03), Edit::None);
+    assert_eq!(editor.push(b'\r'), Edit::None);
+}
+
+#[test]
+fn long_lines_and_control_bytes_are_dropped() {
+    let mut editor: LineEditor<4> = LineEditor::new();
+    assert_eq!(type_in(&mut editor, b"tick"), Edit::Echo(b'k'));
+    assert_eq!(editor.push(b' '), Edit::None);
+    assert_eq!(editor.push(0x1B), Edit::None);
+    assert_eq!(editor.push(0x80), Edit::None);
+    assert_eq!(editor.push(b'\r'), Edit::Line);
+    assert_eq!(editor.line(), "tick");
+}
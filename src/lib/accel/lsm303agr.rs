@@ -0,0 +1,94 @@
+//! Driver for the accelerometer of the LSM303AGR
+use super::Sample;
+use embedded_hal::i2c::I2c;
+
+/// I2C address of the accelerometer
+pub const ADDRESS: u8 = 0x19;
+/// Expected content of `WHO_AM_I_A`
+const DEVICE_ID: u8 = 0x33;
+
+const WHO_AM_I_A: u8 = 0x0F;
+const CTRL_REG1_A: u8 = 0x20;
+const CTRL_REG4_A: u8 = 0x23;
+const OUT_X_L_A: u8 = 0x28;
+/// Set in a register address to read several registers in one go
+const AUTO_INCREMENT: u8 = 0x80;
+
+/// 100 Hz, X, Y and Z enabled
+const CTRL_REG1_100HZ_XYZ: u8 = 0x57;
+/// Block data update, +/-4 g, high resolution (2 mg per digit). Shakes easily go beyond 2 g.
+const CTRL_REG4_BDU_HR_4G: u8 = 0x98;
+
+/// Errors from the accelerometer
+#[derive(Debug)]
+pub enum Error<E> {
+    I2c(E),
+    /// `WHO_AM_I_A` didn't match
+    UnknownDevice(u8),
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::I2c(e)
+    }
+}
+
+impl<E: embedded_hal::i2c::Error> defmt::Format for Error<E> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            Error::I2c(e) => defmt::write!(f, "I2C: {}", defmt::Debug2Format(&e.kind())),
+            Error::UnknownDevice(id) => defmt::write!(f, "unknown device: {=u8:#x}", id),
+        }
+    }
+}
+
+/// LSM303AGR accelerometer
+///
+/// Buffers handed to the bus are locals as the nRF's EasyDMA can't read from flash.
+pub struct Lsm303agr<I2C> {
+    i2c: I2C,
+}
+
+impl<I2C: I2c> Lsm303agr<I2C> {
+    /// Checks the device id and starts measuring at 100 Hz
+    pub fn new(mut i2c: I2C) -> Result<Self, Error<I2C::Error>> {
+        let reg = [WHO_AM_I_A];
+        let mut id = [0u8; 1];
+        i2c.write_read(ADDRESS, &reg, &mut id)?;
+        if id[0] != DEVICE_ID {
+            return Err(Error::UnknownDevice(id[0]));
+        }
+
+        let mut accel = Lsm303agr { i2c };
+        accel.write_register(CTRL_REG1_A, CTRL_REG1_100HZ_XYZ)?;
+        accel.write_register(CTRL_REG4_A, CTRL_REG4_BDU_HR_4G)?;
+        Ok(accel)
+    }
+
+    /// Reads the latest acceleration
+    pub fn read(&mut self) -> Result<Sample, Error<I2C::Error>> {
+        let reg = [OUT_X_L_A | AUTO_INCREMENT];
+        let mut data = [0u8; 6];
+        self.i2c.write_read(ADDRESS, &reg, &mut data)?;
+
+        // Left-justified 12-bit values
+        let axis = |i: usize| (i16::from_le_bytes([data[i], data[i + 1]]) >> 4) * 2;
+        // The sensor sits on the back of the board, upside down
+        Ok(Sample {
+            x: -axis(0),
+            y: axis(2),
+            z: -axis(4),
+        })
+    }
+
+    /// Releases the bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        let buf = [register, value];
+        self.i2c.write(ADDRESS, &buf)?;
+        Ok(())
+    }
+}
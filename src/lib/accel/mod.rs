@@ -0,0 +1,75 @@
+//! Motion input from the on-board accelerometer
+//!
+//! Turning the board face-down starts a work phase and turning it face-up pauses it. A shake
+//! acknowledges the alarm, skips a rest or undoes a pause, see [`ShakeContext::action`]. The
+//! display rotation follows the edge pointing up.
+use crate::types::{AppCommand, APP_COMMANDS, APP_STATUS, DISPLAY_ROTATION};
+use defmt::*;
+use embassy_nrf::{peripherals::TWISPI0, twim::Twim};
+use embassy_time::{Duration, Instant, Timer};
+
+mod lsm303agr;
+mod orientation;
+mod rotation;
+pub use lsm303agr::*;
+pub use orientation::*;
+pub use pomodoro_detect::{Sample, ShakeAction, ShakeConfig, ShakeContext, ShakeDetector};
+pub use rotation::*;
+
+/// Time between two samples
+const SAMPLE_PERIOD: Duration = Duration::from_millis(20);
+
+/// Accelerometer task
+#[embassy_executor::task]
+pub async fn accel_task(twim: Twim<'static, TWISPI0>, shake: ShakeConfig) {
+    let mut accel = match Lsm303agr::new(twim) {
+        Ok(accel) => accel,
+        Err(e) => {
+            error!("accelerometer: {:?}", e);
+            return;
+        }
+    };
+    let mut flips = FlipDetector::new(FlipDetector::DEBOUNCE, Instant::now());
+    let mut shakes = ShakeDetector::new(shake);
+    let mut rotation = RotationDetector::new();
+    let state = APP_STATUS.lock(|s| s.get()).state;
+    let mut context = ShakeContext::new(state.into(), Instant::now());
+
+    loop {
+        Timer::after(SAMPLE_PERIOD).await;
+        let sample = match accel.read() {
+            Ok(sample) => sample,
+            Err(e) => {
+                warn!("accelerometer: {:?}", e);
+                continue;
+            }
+        };
+
+        if let Some(rotation) = rotation.update(&sample) {
+            debug!("rotation: {:?}", rotation);
+            DISPLAY_ROTATION.lock(|r| r.set(rotation));
+        }
+
+        let now = Instant::now();
+        context.observe(APP_STATUS.lock(|s| s.get()).state.into(), now);
+
+        let command = if shakes.update(&sample, now) {
+            info!("shaken");
+            context.action(now).map(|action| match action {
+                ShakeAction::Acknowledge => AppCommand::Acknowledge,
+                ShakeAction::Skip => AppCommand::Skip,
+                ShakeAction::Resume => AppCommand::Start,
+            })
+        } else {
+            match flips.update(&sample, now) {
+                Some(Orientation::FaceDown) => Some(AppCommand::Start),
+                Some(Orientation::FaceUp) => Some(AppCommand::Pause),
+                _ => None,
+            }
+        };
+        if let Some(command) = command {
+            info!("motion: {:?}", command);
+            APP_COMMANDS.try_send(command).ok();
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Face-up / face-down detection with debounce
+use super::Sample;
+use embassy_time::{Duration, Instant};
+
+/// Which way the LEDs face
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Orientation {
+    FaceUp,
+    FaceDown,
+    /// On an edge or in hand
+    Tilted,
+}
+
+impl Orientation {
+    /// Acceleration along z in mg above which the board lies flat
+    pub const FLAT_THRESHOLD: i16 = 800;
+
+    /// Classifies a single sample
+    pub fn of(sample: &Sample) -> Orientation {
+        match sample.z {
+            z if z >= Self::FLAT_THRESHOLD => Orientation::FaceUp,
+            z if z <= -Self::FLAT_THRESHOLD => Orientation::FaceDown,
+            _ => Orientation::Tilted,
+        }
+    }
+}
+
+/// Reports flips between face-up and face-down once the board has settled
+///
+/// Picking the board up and putting it back the same way isn't a flip. The orientation at
+/// boot isn't reported either.
+pub struct FlipDetector {
+    debounce: Duration,
+    /// Orientation seen since `since`
+    candidate: Orientation,
+    since: Instant,
+    /// Last settled flat orientation
+    flat: Option<Orientation>,
+}
+
+impl FlipDetector {
+    /// Default time the board has to lie still before a flip is reported
+    pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+    pub fn new(debounce: Duration, now: Instant) -> Self {
+        FlipDetector {
+            debounce,
+            candidate: Orientation::Tilted,
+            since: now,
+            flat: None,
+        }
+    }
+
+    /// Feeds a sample. Returns the new orientation after a flip.
+    pub fn update(&mut self, sample: &Sample, now: Instant) -> Option<Orientation> {
+        let orientation = Orientation::of(sample);
+        if orientation != self.candidate {
+            self.candidate = orientation;
+            self.since = now;
+            return None;
+        }
+
+        if orientation == Orientation::Tilted
+            || Some(orientation) == self.flat
+            || now - self.since < self.debounce
+        {
+            return None;
+        }
+
+        let first = self.flat.is_none();
+        self.flat = Some(orientation);
+        (!first).then_some(orientation)
+    }
+}
@@ -0,0 +1,76 @@
+//! Display rotation from the direction of gravity
+use super::Sample;
+use crate::display::Rotation;
+
+/// Picks the display rotation that keeps the icons upright
+///
+/// Only the x and y axes count, so the rotation is kept while the board lies flat.
+pub struct RotationDetector {
+    rotation: Rotation,
+}
+
+impl RotationDetector {
+    /// Acceleration in mg in the plane of the board below which it counts as lying flat
+    pub const MIN_TILT: i32 = 500;
+    /// How much more mg the new edge has to point up than the current one before rotating.
+    /// 300 mg is roughly 15 degrees past the diagonal.
+    pub const HYSTERESIS: i32 = 300;
+
+    pub const fn new() -> Self {
+        RotationDetector {
+            rotation: Rotation::R0,
+        }
+    }
+
+    /// Current rotation
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Feeds a sample. Returns the new rotation when it changes.
+    pub fn update(&mut self, sample: &Sample) -> Option<Rotation> {
+        let (x, y) = (sample.x as i32, sample.y as i32);
+        if x * x + y * y < Self::MIN_TILT * Self::MIN_TILT {
+            return None;
+        }
+
+        // The edge pointing up the most
+        let candidate = if x.abs() > y.abs() {
+            if x > 0 {
+                Rotation::R90
+            } else {
+                Rotation::R270
+            }
+        } else if y > 0 {
+            Rotation::R0
+        } else {
+            Rotation::R180
+        };
+
+        if candidate == self.rotation
+            || up(candidate, sample) - up(self.rotation, sample) < Self::HYSTERESIS
+        {
+            return None;
+        }
+        self.rotation = candidate;
+        Some(candidate)
+    }
+}
+
+impl Default for RotationDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How much the top edge of the rotated display points up, in mg
+fn up(rotation: Rotation, sample: &Sample) -> i32 {
+    match rotation {
+        // USB port on top
+        Rotation::R0 => sample.y as i32,
+        // The right edge is on top, so the board has been turned counter-clockwise
+        Rotation::R90 => sample.x as i32,
+        Rotation::R180 => -(sample.y as i32),
+        Rotation::R270 => -(sample.x as i32),
+    }
+}
@@ -0,0 +1,54 @@
+//! Alarm at the end of a phase
+//!
+//! With the alarm turned on, a finished phase rings until it is acknowledged with a button and
+//! the next phase only starts then. The alarm starts at level 1 and goes up a level every few
+//! rings: each level is louder, higher and rings more often, up to [`MAX_LEVEL`].
+use embassy_time::{Duration, Instant};
+
+/// Highest level
+pub const MAX_LEVEL: u8 = 3;
+/// Rings before the alarm goes up a level
+const RINGS_PER_LEVEL: u32 = 3;
+
+/// A ringing alarm
+#[derive(Clone, Copy, Debug)]
+pub struct Alarm {
+    started: Instant,
+    next_ring: Instant,
+    rings: u32,
+}
+
+impl Alarm {
+    /// Creates an alarm that rings straight away
+    pub fn new(now: Instant) -> Self {
+        Alarm {
+            started: now,
+            next_ring: now,
+            rings: 0,
+        }
+    }
+
+    /// Level of the next ring, from 1 to [`MAX_LEVEL`]
+    pub fn level(&self) -> u8 {
+        (1 + self.rings / RINGS_PER_LEVEL).min(MAX_LEVEL as u32) as u8
+    }
+
+    /// Returns the level to ring at if a ring is due
+    pub fn poll(&mut self, now: Instant) -> Option<u8> {
+        if now < self.next_ring {
+            return None;
+        }
+        let level = self.level();
+        self.rings += 1;
+        // Every 3 seconds at level 1 down to every second at the top level. Late polls don't
+        // delay the rings after them.
+        let interval = Duration::from_secs((MAX_LEVEL + 1 - level) as u64);
+        self.next_ring = (self.next_ring + interval).max(now);
+        Some(level)
+    }
+
+    /// How long the alarm has been ringing
+    pub fn duration(&self, now: Instant) -> Duration {
+        now - self.started
+    }
+}
@@ -0,0 +1,222 @@
+//! Advertising policy
+//!
+//! Advertises fast for a while after boot or a button press, then slow. Optionally stops
+//! advertising while a focus session runs until a button is pressed. A group leader never stops
+//! advertising as its advertising packets carry the sync beacon, and it keeps advertising the
+//! beacon while a client is connected.
+use super::sync::SyncBeacon;
+use crate::types::{State, ADV_EVENTS};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use embassy_time::{Duration, Instant};
+
+/// Time spent in each advertising mode
+pub static ADV_STATS: Mutex<ThreadModeRawMutex, Cell<AdvStats>> =
+    Mutex::new(Cell::new(AdvStats::new()));
+/// Latest app state, read by the BLE task each time it picks a mode
+pub static ADV_APP_STATE: Mutex<ThreadModeRawMutex, Cell<State>> =
+    Mutex::new(Cell::new(State::Paused));
+/// Latest sync beacon to broadcast as a group leader
+pub static ADV_BEACON: Mutex<ThreadModeRawMutex, Cell<Option<SyncBeacon>>> =
+    Mutex::new(Cell::new(None));
+
+/// Advertising mode
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub enum AdvMode {
+    /// Advertising at the fast interval
+    Fast,
+    /// Advertising at the slow interval
+    Slow,
+    /// Not advertising
+    Off,
+}
+
+/// Events that drive the advertising policy
+#[derive(Copy, Clone, Debug, defmt::Format)]
+pub enum AdvEvent {
+    /// A button has been pressed
+    ButtonPressed,
+    /// The app moved to a new `State`
+    StateChanged(State),
+}
+
+/// Inputs of the advertising policy, updated from [`AdvEvent`]s and the latest beacon
+#[derive(Copy, Clone, Debug)]
+pub struct AdvState {
+    /// Boot or the last button press
+    pub last_wake: Instant,
+    /// Current app state
+    pub state: State,
+    /// Whether a button has been pressed since the focus session started
+    pub woken: bool,
+    /// Sync beacon to broadcast as a group leader
+    pub beacon: Option<SyncBeacon>,
+}
+
+impl AdvState {
+    /// Creates the state at boot
+    pub fn new(now: Instant) -> Self {
+        AdvState {
+            last_wake: now,
+            state: State::Paused,
+            woken: false,
+            beacon: None,
+        }
+    }
+
+    /// Applies an advertising event
+    pub fn apply(&mut self, event: AdvEvent, now: Instant) {
+        match event {
+            AdvEvent::ButtonPressed => {
+                self.last_wake = now;
+                self.woken = true;
+            }
+            AdvEvent::StateChanged(state) => {
+                // A new focus session starts asleep
+                if state == State::Running && self.state != State::Running {
+                    self.woken = false;
+                }
+                self.state = state;
+            }
+        }
+    }
+}
+
+/// Advertising policy
+#[derive(Copy, Clone, Debug)]
+pub struct AdvertisingPolicy {
+    /// Advertising interval in fast mode
+    pub fast_interval: Duration,
+    /// How long to stay in fast mode after boot or a button press
+    pub fast_duration: Duration,
+    /// Advertising interval in slow mode
+    pub slow_interval: Duration,
+    /// Stop advertising while `State::Running` until a button is pressed
+    pub stop_while_running: bool,
+    /// Minimum connection interval requested once connected
+    pub min_conn_interval: Duration,
+    /// Maximum connection interval requested once connected
+    pub max_conn_interval: Duration,
+    /// Number of connection events the peripheral may skip
+    pub slave_latency: u16,
+    /// Connection supervision timeout
+    pub supervision_timeout: Duration,
+}
+
+impl Default for AdvertisingPolicy {
+    fn default() -> Self {
+        AdvertisingPolicy {
+            fast_interval: Duration::from_millis(100),
+            fast_duration: Duration::from_secs(30),
+            slow_interval: Duration::from_millis(1000),
+            stop_while_running: false,
+            min_conn_interval: Duration::from_millis(100),
+            max_conn_interval: Duration::from_millis(200),
+            slave_latency: 4,
+            supervision_timeout: Duration::from_secs(4),
+        }
+    }
+}
+
+impl AdvertisingPolicy {
+    /// Returns the advertising mode
+    pub fn mode(&self, adv: &AdvState, now: Instant) -> AdvMode {
+        if now - adv.last_wake < self.fast_duration {
+            AdvMode::Fast
+        } else if self.stop_while_running
+            && adv.state == State::Running
+            && !adv.woken
+            && adv.beacon.is_none()
+        {
+            AdvMode::Off
+        } else {
+            AdvMode::Slow
+        }
+    }
+
+    /// Returns how long fast mode lasts from `now`
+    pub fn fast_remaining(&self, adv: &AdvState, now: Instant) -> Duration {
+        let since_wake = now - adv.last_wake;
+        if since_wake < self.fast_duration {
+            self.fast_duration - since_wake
+        } else {
+            Duration::from_ticks(0)
+        }
+    }
+
+    /// Returns the advertising interval for a mode in units of 0.625 ms
+    pub fn interval_units(&self, mode: AdvMode) -> u32 {
+        let interval = match mode {
+            AdvMode::Fast => self.fast_interval,
+            AdvMode::Slow | AdvMode::Off => self.slow_interval,
+        };
+        (interval.as_micros() / 625) as u32
+    }
+}
+
+/// Time spent in each advertising mode and connected
+#[derive(Copy, Clone, Debug, defmt::Format)]
+pub struct AdvStats {
+    /// Time spent advertising fast, in milliseconds
+    pub fast_ms: u64,
+    /// Time spent advertising slow, in milliseconds
+    pub slow_ms: u64,
+    /// Time spent not advertising, in milliseconds
+    pub off_ms: u64,
+    /// Time spent connected, in milliseconds
+    pub connected_ms: u64,
+}
+
+impl AdvStats {
+    /// Creates zeroed stats
+    pub const fn new() -> Self {
+        AdvStats {
+            fast_ms: 0,
+            slow_ms: 0,
+            off_ms: 0,
+            connected_ms: 0,
+        }
+    }
+
+    /// Adds time spent in a mode
+    pub fn record(&mut self, mode: AdvMode, elapsed: Duration) {
+        let ms = elapsed.as_millis();
+        match mode {
+            AdvMode::Fast => self.fast_ms += ms,
+            AdvMode::Slow => self.slow_ms += ms,
+            AdvMode::Off => self.off_ms += ms,
+        }
+    }
+
+    /// Adds time spent connected
+    pub fn record_connected(&mut self, elapsed: Duration) {
+        self.connected_ms += elapsed.as_millis();
+    }
+}
+
+impl Default for AdvStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Updates the global advertising stats
+pub fn update_stats(f: impl FnOnce(&mut AdvStats)) {
+    ADV_STATS.lock(|stats| {
+        let mut s = stats.get();
+        f(&mut s);
+        stats.set(s);
+    });
+}
+
+/// Publishes a new app state, and wakes the BLE task to pick a mode for it
+pub fn publish_state(state: State) {
+    ADV_APP_STATE.lock(|s| s.set(state));
+    // The state is read again on each pass, so a wake-up dropped while connected is harmless
+    ADV_EVENTS.try_send(AdvEvent::StateChanged(state)).ok();
+}
+
+/// Publishes the sync beacon of a group leader
+pub fn publish_beacon(beacon: SyncBeacon) {
+    ADV_BEACON.lock(|b| b.set(Some(beacon)));
+}
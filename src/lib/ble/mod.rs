@@ -1,4 +1,8 @@
 //! BLE types and functions
+pub mod advertising;
 pub mod sd;
 pub mod server;
 pub mod services;
+pub mod sync;
+pub mod transfer;
+pub mod uuids;
@@ -26,8 +26,9 @@ pub fn softdevice_config() -> nrf_softdevice::Config {
         }),
         gap_role_count: Some(raw::ble_gap_cfg_role_count_t {
             adv_set_count: 1,
-            periph_role_count: 4,
-            central_role_count: 0,
+            periph_role_count: 3,
+            // Observer role for following a group leader
+            central_role_count: 1,
             central_sec_count: 0,
             _bitfield_1: raw::ble_gap_cfg_role_count_t::new_bitfield_1(0),
         }),
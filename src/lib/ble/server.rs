@@ -1,14 +1,36 @@
 //! BLE Server Config and Tasks
-use super::services::*;
-use crate::types::CONFIG_SIGNAL;
+use super::{
+    advertising::{
+        update_stats, AdvEvent, AdvMode, AdvState, AdvertisingPolicy, ADV_APP_STATE, ADV_BEACON,
+    },
+    services::*,
+    sync::{SyncBeacon, BEACON_AD_LEN},
+    transfer::Reassembler,
+    uuids,
+};
+use crate::{
+    events::ConfigSource,
+    sound::{Volume, MAX_TICK_INTERVAL},
+    types::{
+        config_report, AppCommand, ConfigError, Payload, ADV_EVENTS, APP_COMMANDS,
+        CONFIG_RESULT_SIGNAL, CONFIG_SIGNAL, RINGTONES,
+    },
+};
 use defmt::*;
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_time::{Duration, Instant, Timer};
 use nrf_softdevice::{
-    ble::{gatt_server, peripheral},
+    ble::{
+        gatt_server,
+        peripheral::{self, AdvertiseError},
+        Connection,
+    },
     raw::{
-        BLE_GAP_ADV_FLAGS_LE_ONLY_GENERAL_DISC_MODE, BLE_GAP_AD_TYPE_128BIT_SERVICE_UUID_COMPLETE,
-        BLE_GAP_AD_TYPE_COMPLETE_LOCAL_NAME, BLE_GAP_AD_TYPE_FLAGS,
+        self, BLE_GAP_ADV_FLAGS_LE_ONLY_GENERAL_DISC_MODE,
+        BLE_GAP_AD_TYPE_128BIT_SERVICE_UUID_COMPLETE, BLE_GAP_AD_TYPE_COMPLETE_LOCAL_NAME,
+        BLE_GAP_AD_TYPE_FLAGS,
     },
-    Softdevice,
+    RawError, Softdevice,
 };
 
 /// BLE advertising data
@@ -19,17 +41,35 @@ const ADV_DATA: &[u8; 14] =
         0x0a, BLE_GAP_AD_TYPE_COMPLETE_LOCAL_NAME as u8, b'P', b'o', b'm', b'o', b'd', b'o', b'r', b'o', b'!'
     ];
 
+/// Size of the advertising data of a group leader
+const LEADER_ADV_DATA_LEN: usize = ADV_DATA.len() + BEACON_AD_LEN;
+/// How often a group leader updates the beacon it advertises
+const BEACON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Advertising data of a group leader. The SoftDevice keeps reading the buffer it was handed
+/// while advertising, so updates alternate between the two.
+static mut LEADER_ADV_DATA: [[u8; LEADER_ADV_DATA_LEN]; 2] = [[0; LEADER_ADV_DATA_LEN]; 2];
+/// Buffer of [`LEADER_ADV_DATA`] written last
+static mut LEADER_ADV_BUFFER: usize = 0;
+
 /// BLE scan response data
-#[rustfmt::skip]
-const SCAN_RESPONSE_DATA: &[u8; 18] = &[
+const SCAN_RESPONSE_DATA: &[u8; 18] = &scan_response_data();
+
+/// Builds the scan response data: the complete list of 128-bit service UUIDs
+const fn scan_response_data() -> [u8; 18] {
+    let mut data = [0u8; 18];
     // AD length
-    0x11, 
+    data[0] = 0x11;
     // AD type
-    BLE_GAP_AD_TYPE_128BIT_SERVICE_UUID_COMPLETE as u8,
-    // AD data
-    // Configuration service UUID: 11111111-1111-1111-1111-111111111122. This has to be sent in little endian order.
-    0x22, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
-];
+    data[1] = BLE_GAP_AD_TYPE_128BIT_SERVICE_UUID_COMPLETE as u8;
+    // AD data: Configuration service UUID in little endian order
+    let mut i = 0;
+    while i < uuids::CONFIG_SERVICE.len() {
+        data[2 + i] = uuids::CONFIG_SERVICE[i];
+        i += 1;
+    }
+    data
+}
 
 /// BLE GATT server
 #[nrf_softdevice::gatt_server]
@@ -40,29 +80,246 @@ pub struct Server {
 
 /// GATT server task
 #[embassy_executor::task]
-pub async fn ble_server_task(server: Server, sd: &'static Softdevice) {
+pub async fn ble_server_task(server: Server, sd: &'static Softdevice, policy: AdvertisingPolicy) {
     info!("Bluetooth ON!");
 
-    let config = peripheral::Config::default();
-    let adv = peripheral::ConnectableAdvertisement::ScannableUndirected {
-        adv_data: ADV_DATA,
-        scan_data: SCAN_RESPONSE_DATA,
-    };
+    let mut adv_state = AdvState::new(Instant::now());
 
     loop {
-        match peripheral::advertise_connectable(sd, adv, &config).await {
-            Ok(conn) => {
-                if let Err(e) = gatt_server::run(&conn, &server, |e| match e {
-                    ServerEvent::Config(ConfigServiceEvent::BytesWrite(vec)) => {
-                        CONFIG_SIGNAL.signal(vec);
+        refresh(&mut adv_state);
+        let started = Instant::now();
+        let mode = policy.mode(&adv_state, started);
+        debug!("advertising: {:?}", mode);
+
+        let mut config = peripheral::Config::default();
+        config.interval = policy.interval_units(mode);
+        if mode == AdvMode::Fast {
+            // Time out when fast mode is over so that we switch to slow mode
+            let remaining = policy.fast_remaining(&adv_state, started).as_millis() / 10;
+            config.timeout = Some(remaining.clamp(1, u16::MAX as u64) as u16);
+        }
+
+        // Group leaders append their sync beacon
+        let adv_data = match &adv_state.beacon {
+            Some(beacon) => leader_adv_data(beacon),
+            None => ADV_DATA,
+        };
+        let adv = peripheral::ConnectableAdvertisement::ScannableUndirected {
+            adv_data,
+            scan_data: SCAN_RESPONSE_DATA,
+        };
+
+        let event = match mode {
+            AdvMode::Off => Some(ADV_EVENTS.recv().await),
+            AdvMode::Fast | AdvMode::Slow => {
+                let advertise = peripheral::advertise_connectable(sd, adv, &config);
+                let beacon = update_beacon(Some(SCAN_RESPONSE_DATA));
+                match select3(advertise, ADV_EVENTS.recv(), beacon).await {
+                    Either3::First(Ok(conn)) => {
+                        update_stats(|s| s.record(mode, Instant::now() - started));
+                        request_conn_params(&conn, &policy);
+                        let connected = Instant::now();
+                        // Advertising stops once connected, so leaders carry on with a beacon
+                        // that can't be connected to
+                        select(serve(sd, &conn, &server), broadcast_beacon(sd, &policy)).await;
+                        update_stats(|s| s.record_connected(Instant::now() - connected));
+                        continue;
+                    }
+                    Either3::First(Err(AdvertiseError::Timeout)) => None,
+                    Either3::First(Err(e)) => {
+                        error!("{:?}", e);
+                        None
+                    }
+                    Either3::Second(event) => Some(event),
+                    Either3::Third(_) => unreachable!(),
+                }
+            }
+        };
+
+        update_stats(|s| s.record(mode, Instant::now() - started));
+        if let Some(event) = event {
+            adv_state.apply(event, Instant::now());
+        }
+    }
+}
+
+/// Runs the GATT server until the connection is closed
+async fn serve(sd: &Softdevice, conn: &Connection, server: &Server) {
+    // Results for an earlier client, or for no client, aren't for this one
+    CONFIG_RESULT_SIGNAL.reset();
+    let mut reassembler = Reassembler::new();
+    let mut ringtone_reassembler = Reassembler::new();
+    let run = gatt_server::run(conn, server, |e| match e {
+        ServerEvent::Config(ConfigServiceEvent::BytesWrite(vec)) => {
+            CONFIG_SIGNAL.signal(unwrap!(Payload::from_slice(&vec)));
+        }
+        ServerEvent::Config(ConfigServiceEvent::TransferWrite(frame)) => {
+            let (ack, payload) = reassembler.push(&frame);
+            debug!("transfer: {:?}", ack);
+            if let Err(e) = server.config.transfer_ack_notify(conn, &ack.to_bytes()) {
+                warn!("{:?}", e);
+            }
+            if let Some(payload) = payload {
+                CONFIG_SIGNAL.signal(payload);
+            }
+        }
+        ServerEvent::Config(ConfigServiceEvent::RingtoneWrite(frame)) => {
+            let (ack, payload) = ringtone_reassembler.push(&frame);
+            debug!("ringtone transfer: {:?}", ack);
+            if let Err(e) = server.config.transfer_ack_notify(conn, &ack.to_bytes()) {
+                warn!("{:?}", e);
+            }
+            if let Some(payload) = payload {
+                // Valid ringtones are reported once they are saved
+                match RINGTONES.lock(|r| r.borrow_mut().upload(&payload)) {
+                    Ok(sound) => {
+                        info!("ringtone uploaded: {:?}", sound);
+                        APP_COMMANDS.try_send(AppCommand::SaveRingtones).ok();
+                    }
+                    Err(e) => {
+                        warn!("{:?}", e);
+                        CONFIG_RESULT_SIGNAL.signal(config_report(Err(&e)));
                     }
-                })
-                .await
-                {
-                    error!("{:?}", e);
                 }
             }
-            Err(e) => error!("{:?}", e),
         }
+        ServerEvent::Config(ConfigServiceEvent::VolumeWrite(level)) => {
+            // Valid volumes are reported once they are saved
+            if level > Volume::MAX.level() {
+                let e = ConfigError::InvalidVolume(level);
+                warn!("{:?}", e);
+                CONFIG_RESULT_SIGNAL.signal(config_report(Err(&e)));
+            } else {
+                APP_COMMANDS
+                    .try_send(AppCommand::SetVolume(Volume::new(level), ConfigSource::Ble))
+                    .ok();
+            }
+        }
+        ServerEvent::Config(ConfigServiceEvent::TickWrite(interval)) => {
+            if interval > MAX_TICK_INTERVAL {
+                let e = ConfigError::InvalidTick(interval);
+                warn!("{:?}", e);
+                CONFIG_RESULT_SIGNAL.signal(config_report(Err(&e)));
+            } else {
+                APP_COMMANDS
+                    .try_send(AppCommand::SetTick(interval, ConfigSource::Ble))
+                    .ok();
+            }
+        }
+        ServerEvent::Config(ConfigServiceEvent::TransferAckCccdWrite { .. }) => {}
+        ServerEvent::Config(ConfigServiceEvent::ConfigResultCccdWrite { .. }) => {}
+    });
+
+    // Report the result of each config request back to the client
+    let report = async {
+        loop {
+            let report = CONFIG_RESULT_SIGNAL.wait().await;
+            if let Err(e) = server.config.config_result_set(sd, &report) {
+                warn!("{:?}", e);
+            }
+            if let Err(e) = server.config.config_result_notify(conn, &report) {
+                warn!("{:?}", e);
+            }
+        }
+    };
+
+    if let Either::First(Err(e)) = select(run, report).await {
+        error!("{:?}", e);
+    }
+}
+
+/// Broadcasts the beacon of a group leader while connected. Devices that don't lead never get
+/// a beacon to broadcast. Never returns, so only the connection ends the connected branch.
+async fn broadcast_beacon(sd: &Softdevice, policy: &AdvertisingPolicy) {
+    let beacon = loop {
+        if let Some(beacon) = ADV_BEACON.lock(|b| b.get()) {
+            break beacon;
+        }
+        Timer::after(BEACON_INTERVAL).await;
+    };
+
+    let mut config = peripheral::Config::default();
+    config.interval = policy.interval_units(AdvMode::Slow);
+    let adv = peripheral::NonconnectableAdvertisement::NonscannableUndirected {
+        adv_data: leader_adv_data(&beacon),
+    };
+    let advertise = peripheral::advertise(sd, adv, &config);
+    if let Either::First(Err(e)) = select(advertise, update_beacon(None)).await {
+        error!("{:?}", e);
+    }
+    // Keep serving the client without a beacon
+    core::future::pending::<()>().await;
+}
+
+/// Keeps the advertised beacon of a group leader up to date without restarting advertising.
+/// Never returns.
+async fn update_beacon(scan_data: Option<&'static [u8]>) {
+    loop {
+        Timer::after(BEACON_INTERVAL).await;
+        let Some(beacon) = ADV_BEACON.lock(|b| b.get()) else {
+            continue;
+        };
+        let adv_data = leader_adv_data(&beacon);
+        let scan_data = scan_data.unwrap_or(&[]);
+        let data = raw::ble_gap_adv_data_t {
+            adv_data: raw::ble_data_t {
+                p_data: adv_data.as_ptr() as *mut u8,
+                len: adv_data.len() as u16,
+            },
+            scan_rsp_data: raw::ble_data_t {
+                p_data: match scan_data.is_empty() {
+                    true => core::ptr::null_mut(),
+                    false => scan_data.as_ptr() as *mut u8,
+                },
+                len: scan_data.len() as u16,
+            },
+        };
+        // There is a single advertising set, and no parameters means only the data changes
+        let mut handle = 0;
+        // SAFETY: the data is static and stays untouched until the next update
+        let ret =
+            unsafe { raw::sd_ble_gap_adv_set_configure(&mut handle, &data, core::ptr::null()) };
+        if let Err(e) = RawError::convert(ret) {
+            warn!("{:?}", e);
+        }
+    }
+}
+
+/// Writes the advertising data with a beacon to the buffer the SoftDevice isn't reading
+fn leader_adv_data(beacon: &SyncBeacon) -> &'static [u8] {
+    // SAFETY: only the BLE server task touches the buffers, and the SoftDevice only reads the
+    // buffer it was handed last
+    unsafe {
+        LEADER_ADV_BUFFER ^= 1;
+        let buf = &mut LEADER_ADV_DATA[LEADER_ADV_BUFFER];
+        buf[..ADV_DATA.len()].copy_from_slice(ADV_DATA);
+        buf[ADV_DATA.len()..].copy_from_slice(&beacon.to_ad());
+        buf
+    }
+}
+
+/// Requests the connection intervals of the advertising policy
+fn request_conn_params(conn: &Connection, policy: &AdvertisingPolicy) {
+    // Connection intervals are in units of 1.25 ms, the supervision timeout in units of 10 ms
+    let params = raw::ble_gap_conn_params_t {
+        min_conn_interval: (policy.min_conn_interval.as_micros() / 1250) as u16,
+        max_conn_interval: (policy.max_conn_interval.as_micros() / 1250) as u16,
+        slave_latency: policy.slave_latency,
+        conn_sup_timeout: (policy.supervision_timeout.as_millis() / 10) as u16,
+    };
+    if let Err(e) = conn.set_conn_params(params) {
+        warn!("{:?}", e);
+    }
+}
+
+/// Applies the wake-ups received while advertising or connected, then the latest app state and
+/// beacon
+fn refresh(adv_state: &mut AdvState) {
+    let now = Instant::now();
+    while let Ok(event) = ADV_EVENTS.try_recv() {
+        adv_state.apply(event, now);
     }
+    let state = ADV_APP_STATE.lock(|s| s.get());
+    adv_state.apply(AdvEvent::StateChanged(state), now);
+    adv_state.beacon = ADV_BEACON.lock(|b| b.get());
 }
@@ -1,8 +1,223 @@
 //! BLE GATT services
+//!
+//! The services are registered with the SoftDevice's service builder instead of the
+//! `gatt_service` macro, which can't attach User Description and Presentation Format
+//! descriptors to a characteristic.
+use super::{transfer, uuids};
+use crate::types::ConfigReport;
+use nrf_softdevice::{
+    ble::{
+        gatt_server::{
+            self,
+            builder::ServiceBuilder,
+            characteristic::{Attribute, Metadata, Presentation, Properties, UserDescription},
+            CharacteristicHandles, NotifyValueError, RegisterError, Service, SetValueError,
+        },
+        Connection, GattValue, Uuid,
+    },
+    Softdevice,
+};
+
+/// Presentation Format: unsigned 8-bit integer
+const FORMAT_UINT8: u8 = 0x04;
+/// Presentation Format: UTF-8 string
+const FORMAT_UTF8: u8 = 0x19;
+/// Presentation Format: opaque structure
+const FORMAT_STRUCT: u8 = 0x1B;
+/// Presentation Format: unitless
+const UNIT_UNITLESS: u16 = 0x2700;
+/// Presentation Format: Bluetooth SIG name space
+const NAME_SPACE_SIG: u8 = 0x01;
 
 /// Configuration service
-#[nrf_softdevice::gatt_service(uuid = "11111111-1111-1111-1111-111111111122")]
 pub struct ConfigService {
-    #[characteristic(uuid = "11111111-1111-1111-1111-111111111122", read, write)]
-    pub bytes: heapless::Vec<u8, 32>,
+    bytes: CharacteristicHandles,
+    transfer: CharacteristicHandles,
+    transfer_ack: CharacteristicHandles,
+    config_result: CharacteristicHandles,
+    ringtone: CharacteristicHandles,
+    volume: CharacteristicHandles,
+    tick: CharacteristicHandles,
+}
+
+/// Events of the configuration service
+pub enum ConfigServiceEvent {
+    /// `"work,rest"` string written to the config characteristic
+    BytesWrite(heapless::Vec<u8, 32>),
+    /// Frame written to the transfer characteristic
+    TransferWrite(transfer::Frame),
+    /// Client (un)subscribed to transfer acknowledgements
+    TransferAckCccdWrite { notifications: bool },
+    /// Client (un)subscribed to config results
+    ConfigResultCccdWrite { notifications: bool },
+    /// Frame of a ringtone upload, see [`Ringtones::upload`](crate::ringtones::Ringtones::upload)
+    RingtoneWrite(transfer::Frame),
+    /// New speaker volume level
+    VolumeWrite(u8),
+    /// New seconds between ticks
+    TickWrite(u8),
+}
+
+impl ConfigService {
+    /// Registers the service with the SoftDevice
+    pub fn new(sd: &mut Softdevice) -> Result<Self, RegisterError> {
+        let mut service = ServiceBuilder::new(sd, Uuid::new_128(&uuids::CONFIG_SERVICE))?;
+
+        let bytes = add_characteristic::<heapless::Vec<u8, 32>>(
+            &mut service,
+            &uuids::CONFIG_CHAR,
+            Properties::new().read().write(),
+            b"Timer config",
+            FORMAT_UTF8,
+        )?;
+        let transfer = add_characteristic::<transfer::Frame>(
+            &mut service,
+            &uuids::TRANSFER_CHAR,
+            Properties::new().write(),
+            b"Chunked transfer",
+            FORMAT_STRUCT,
+        )?;
+        let transfer_ack = add_characteristic::<[u8; 2]>(
+            &mut service,
+            &uuids::TRANSFER_ACK_CHAR,
+            Properties::new().read().notify(),
+            b"Transfer ack",
+            FORMAT_STRUCT,
+        )?;
+        let config_result = add_characteristic::<ConfigReport>(
+            &mut service,
+            &uuids::CONFIG_RESULT_CHAR,
+            Properties::new().read().notify(),
+            b"Config result",
+            FORMAT_STRUCT,
+        )?;
+        let ringtone = add_characteristic::<transfer::Frame>(
+            &mut service,
+            &uuids::RINGTONE_CHAR,
+            Properties::new().write(),
+            b"Ringtone",
+            FORMAT_STRUCT,
+        )?;
+        let volume = add_characteristic::<u8>(
+            &mut service,
+            &uuids::VOLUME_CHAR,
+            Properties::new().write(),
+            b"Volume",
+            FORMAT_UINT8,
+        )?;
+        let tick = add_characteristic::<u8>(
+            &mut service,
+            &uuids::TICK_CHAR,
+            Properties::new().write(),
+            b"Tick",
+            FORMAT_UINT8,
+        )?;
+
+        let _ = service.build();
+
+        Ok(ConfigService {
+            bytes,
+            transfer,
+            transfer_ack,
+            config_result,
+            ringtone,
+            volume,
+            tick,
+        })
+    }
+
+    /// Notifies a transfer acknowledgement
+    pub fn transfer_ack_notify(
+        &self,
+        conn: &Connection,
+        val: &[u8; 2],
+    ) -> Result<(), NotifyValueError> {
+        gatt_server::notify_value(conn, self.transfer_ack.value_handle, val)
+    }
+
+    /// Sets the config result so that clients can read it
+    pub fn config_result_set(
+        &self,
+        sd: &Softdevice,
+        val: &ConfigReport,
+    ) -> Result<(), SetValueError> {
+        gatt_server::set_value(sd, self.config_result.value_handle, val)
+    }
+
+    /// Notifies a config result
+    pub fn config_result_notify(
+        &self,
+        conn: &Connection,
+        val: &ConfigReport,
+    ) -> Result<(), NotifyValueError> {
+        gatt_server::notify_value(conn, self.config_result.value_handle, val)
+    }
+}
+
+impl Service for ConfigService {
+    type Event = ConfigServiceEvent;
+
+    fn on_write(&self, handle: u16, data: &[u8]) -> Option<Self::Event> {
+        if handle == self.bytes.value_handle {
+            return Some(ConfigServiceEvent::BytesWrite(GattValue::from_gatt(data)));
+        }
+        if handle == self.transfer.value_handle {
+            return Some(ConfigServiceEvent::TransferWrite(GattValue::from_gatt(
+                data,
+            )));
+        }
+        if handle == self.ringtone.value_handle {
+            return Some(ConfigServiceEvent::RingtoneWrite(GattValue::from_gatt(
+                data,
+            )));
+        }
+        if handle == self.volume.value_handle {
+            return Some(ConfigServiceEvent::VolumeWrite(GattValue::from_gatt(data)));
+        }
+        if handle == self.tick.value_handle {
+            return Some(ConfigServiceEvent::TickWrite(GattValue::from_gatt(data)));
+        }
+        if handle == self.transfer_ack.cccd_handle && !data.is_empty() {
+            return Some(ConfigServiceEvent::TransferAckCccdWrite {
+                notifications: data[0] & 0x01 != 0,
+            });
+        }
+        if handle == self.config_result.cccd_handle && !data.is_empty() {
+            return Some(ConfigServiceEvent::ConfigResultCccdWrite {
+                notifications: data[0] & 0x01 != 0,
+            });
+        }
+        None
+    }
+}
+
+/// Adds a characteristic with User Description and Presentation Format descriptors
+fn add_characteristic<T: GattValue>(
+    service: &mut ServiceBuilder,
+    uuid: &[u8; 16],
+    properties: Properties,
+    description: &'static [u8],
+    format: u8,
+) -> Result<CharacteristicHandles, RegisterError> {
+    let initial = [0u8; 2];
+    let mut attr = Attribute::new(&initial[..T::MIN_SIZE]);
+    if T::MAX_SIZE != T::MIN_SIZE {
+        attr = attr.variable_len(T::MAX_SIZE as u16);
+    }
+
+    let metadata = Metadata {
+        user_description: Some(UserDescription::new(description)),
+        presentation: Some(Presentation {
+            format,
+            exponent: 0,
+            unit: UNIT_UNITLESS,
+            name_space: NAME_SPACE_SIG,
+            description: 0,
+        }),
+        ..Metadata::new(properties)
+    };
+
+    Ok(service
+        .add_characteristic(Uuid::new_128(uuid), attr, metadata)?
+        .build())
 }
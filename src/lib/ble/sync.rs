@@ -0,0 +1,196 @@
+//! Team-synchronised pomodoros over BLE broadcast
+//!
+//! A leader puts a [`SyncBeacon`] with its group, `State` and remaining time into the
+//! manufacturer specific data of its advertising packets. Followers scan for beacons of their
+//! group and line their timer up with the leader. If no beacon has been seen for [`LEADER_TIMEOUT`], followers fall back to
+//! local timing.
+use crate::types::State;
+use core::cell::Cell;
+use defmt::*;
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use embassy_time::{Duration, Instant};
+use nrf_softdevice::{ble::central, Softdevice};
+
+/// AD type of manufacturer specific data
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xFF;
+/// Company identifier reserved for testing
+const COMPANY_ID: u16 = 0xFFFF;
+/// Marks a pomodoro sync beacon
+const MAGIC: u8 = b'P';
+
+/// Size of an encoded beacon
+pub const BEACON_LEN: usize = 7;
+/// Size of the manufacturer specific AD structure carrying a beacon
+pub const BEACON_AD_LEN: usize = 4 + BEACON_LEN;
+/// Size of the group id saved to flash: a word
+pub const GROUP_SIZE: usize = 4;
+/// Followers fall back to local timing if the leader hasn't been seen for this long
+pub const LEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Group id of this device, see [`set_group`]
+static GROUP: Mutex<ThreadModeRawMutex, Cell<u8>> = Mutex::new(Cell::new(0));
+
+/// The last beacon seen by a follower and when it was seen
+pub static LAST_BEACON: Mutex<ThreadModeRawMutex, Cell<Option<(Instant, SyncBeacon)>>> =
+    Mutex::new(Cell::new(None));
+
+/// Role of a device in group mode
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub enum GroupRole {
+    /// Local timing only
+    Solo,
+    /// Broadcasts its timer
+    Leader,
+    /// Follows a leader
+    Follower,
+}
+
+impl GroupRole {
+    /// Selects a role from the buttons held at boot: Button A for leader, Button B for follower
+    pub fn from_buttons(button_a: bool, button_b: bool) -> Self {
+        match (button_a, button_b) {
+            (true, false) => GroupRole::Leader,
+            (false, true) => GroupRole::Follower,
+            _ => GroupRole::Solo,
+        }
+    }
+}
+
+/// Phase and remaining time broadcast by a leader
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub struct SyncBeacon {
+    /// Leader's group id
+    pub group: u8,
+    /// Leader's state
+    pub state: State,
+    /// Remaining time of the leader's phase in seconds
+    pub remaining: u32,
+}
+
+impl SyncBeacon {
+    /// Encodes the beacon as `[magic, group, state, remaining (LE)]`
+    pub fn encode(&self) -> [u8; BEACON_LEN] {
+        let state = match self.state {
+            State::Paused => 0,
+            State::Running => 1,
+            State::Resting => 2,
+        };
+        let r = self.remaining.to_le_bytes();
+        [MAGIC, self.group, state, r[0], r[1], r[2], r[3]]
+    }
+
+    /// Decodes a beacon
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != BEACON_LEN || data[0] != MAGIC {
+            return None;
+        }
+        let state = match data[2] {
+            0 => State::Paused,
+            1 => State::Running,
+            2 => State::Resting,
+            _ => return None,
+        };
+        let remaining = u32::from_le_bytes([data[3], data[4], data[5], data[6]]);
+        Some(SyncBeacon {
+            group: data[1],
+            state,
+            remaining,
+        })
+    }
+
+    /// Returns the beacon as a manufacturer specific AD structure
+    pub fn to_ad(&self) -> [u8; BEACON_AD_LEN] {
+        let mut ad = [0u8; BEACON_AD_LEN];
+        ad[0] = (BEACON_AD_LEN - 1) as u8;
+        ad[1] = AD_TYPE_MANUFACTURER_DATA;
+        ad[2..4].copy_from_slice(&COMPANY_ID.to_le_bytes());
+        ad[4..].copy_from_slice(&self.encode());
+        ad
+    }
+
+    /// Looks for a beacon of a group in advertising data
+    pub fn find(adv_data: &[u8], group: u8) -> Option<Self> {
+        let mut rest = adv_data;
+        while let Some((&len, tail)) = rest.split_first() {
+            let len = len as usize;
+            if len == 0 || len > tail.len() {
+                return None;
+            }
+            let (ad, next) = tail.split_at(len);
+            if ad[0] == AD_TYPE_MANUFACTURER_DATA
+                && ad.len() > 3
+                && u16::from_le_bytes([ad[1], ad[2]]) == COMPANY_ID
+            {
+                if let Some(beacon) = Self::decode(&ad[3..]).filter(|b| b.group == group) {
+                    return Some(beacon);
+                }
+            }
+            rest = next;
+        }
+        None
+    }
+
+    /// Returns the state and timer a follower should switch to, or `None` if it is already in
+    /// line with the beacon. `age` is the time since the beacon was received.
+    pub fn align(&self, age: Duration, state: State, timer: u32) -> Option<(State, u32)> {
+        let remaining = self.remaining.saturating_sub(age.as_secs() as u32).max(1);
+        if state == self.state && timer.abs_diff(remaining) <= 1 {
+            None
+        } else {
+            Some((self.state, remaining))
+        }
+    }
+}
+
+/// Sets the group id of this device. Leaders put it into their beacons and followers only
+/// follow leaders of the same group.
+pub fn set_group(group: u8) {
+    GROUP.lock(|g| g.set(group));
+}
+
+/// Group id of this device
+pub fn group() -> u8 {
+    GROUP.lock(|g| g.get())
+}
+
+/// Group id read from flash. Erased flash reads as group 0.
+pub fn group_from_bytes(bytes: [u8; GROUP_SIZE]) -> u8 {
+    match bytes {
+        [group, 0, 0, 0] => group,
+        _ => 0,
+    }
+}
+
+/// Bytes of a group id to write to flash
+pub fn group_to_bytes(group: u8) -> [u8; GROUP_SIZE] {
+    [group, 0, 0, 0]
+}
+
+/// Returns the last beacon if the leader is still around, along with its age
+pub fn last_beacon() -> Option<(SyncBeacon, Duration)> {
+    let (seen, beacon) = LAST_BEACON.lock(|b| b.get())?;
+    let age = Instant::now() - seen;
+    (age < LEADER_TIMEOUT).then_some((beacon, age))
+}
+
+/// Observer task: scans for beacons of a leader
+#[embassy_executor::task]
+pub async fn observer_task(sd: &'static Softdevice) {
+    info!("Group mode: following a leader");
+
+    let config = central::ScanConfig::default();
+    let result = central::scan(sd, &config, |params| {
+        // SAFETY: the SoftDevice guarantees that the report data is valid during the callback
+        let data =
+            unsafe { core::slice::from_raw_parts(params.data.p_data, params.data.len as usize) };
+        if let Some(beacon) = SyncBeacon::find(data, group()) {
+            LAST_BEACON.lock(|b| b.set(Some((Instant::now(), beacon))));
+        }
+        None::<()>
+    })
+    .await;
+
+    if let Err(e) = result {
+        error!("{:?}", e);
+    }
+}
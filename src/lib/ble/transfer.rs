@@ -0,0 +1,182 @@
+//! Framed, chunked transfer for payloads larger than a single GATT write
+//!
+//! Every frame starts with a sequence number. The first frame (sequence `0`) also carries the
+//! total length of the payload and a CRC-16/CCITT-FALSE over the whole payload:
+//!
+//! | Frame | Byte 0 | Bytes 1..3         | Bytes 3..5     | Rest |
+//! |-------|--------|--------------------|----------------|------|
+//! | first | `0`    | total length (LE)  | CRC-16 (LE)    | data |
+//! | next  | `n`    | data               |                |      |
+//!
+//! Each frame is acknowledged with a `[seq, status]` notification (see [`Ack`]). Writing a
+//! frame with sequence `0` always starts a new transfer.
+use crate::types::{Payload, MAX_PAYLOAD_SIZE};
+
+/// Maximum size of a single transfer frame
+pub const FRAME_SIZE: usize = 128;
+/// Header size of the first frame, excluding the sequence number
+const FIRST_HEADER_SIZE: usize = 4;
+
+/// A single transfer frame as written by the client
+pub type Frame = heapless::Vec<u8, FRAME_SIZE>;
+
+/// Status reported back to the client for each frame
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum Status {
+    /// Frame accepted, waiting for more
+    Accepted = 0,
+    /// Last frame accepted and the CRC matches
+    Complete = 1,
+    /// Frame does not follow the previous one. The transfer is aborted.
+    OutOfSequence = 2,
+    /// Payload is larger than announced or than the receive buffer. The transfer is aborted.
+    Overflow = 3,
+    /// CRC of the reassembled payload does not match. The transfer is aborted.
+    CrcMismatch = 4,
+    /// Frame is too short to carry a header
+    Malformed = 5,
+}
+
+/// Acknowledgement for a single frame
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Ack {
+    /// Sequence number of the acknowledged frame
+    pub seq: u8,
+    /// Result of the frame
+    pub status: Status,
+}
+
+impl Ack {
+    /// Returns the acknowledgement as sent over the air
+    pub fn to_bytes(&self) -> [u8; 2] {
+        [self.seq, self.status as u8]
+    }
+}
+
+/// Reassembles frames into a [`Payload`]
+pub struct Reassembler {
+    buf: Payload,
+    expected_len: usize,
+    crc: u16,
+    next_seq: Option<u8>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reassembler {
+    /// Creates an idle reassembler
+    pub const fn new() -> Self {
+        Reassembler {
+            buf: Payload::new(),
+            expected_len: 0,
+            crc: 0,
+            next_seq: None,
+        }
+    }
+
+    /// Drops a transfer in progress
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.expected_len = 0;
+        self.crc = 0;
+        self.next_seq = None;
+    }
+
+    /// Feeds a frame. Returns the acknowledgement for the frame and, once the last frame has
+    /// arrived and the CRC matches, the reassembled payload.
+    pub fn push(&mut self, frame: &[u8]) -> (Ack, Option<Payload>) {
+        let Some((&seq, data)) = frame.split_first() else {
+            return (
+                Ack {
+                    seq: 0,
+                    status: Status::Malformed,
+                },
+                None,
+            );
+        };
+
+        let result = if seq == 0 {
+            self.start(data)
+        } else if self.next_seq == Some(seq) {
+            self.append(data)
+        } else {
+            self.reset();
+            Err(Status::OutOfSequence)
+        };
+
+        match result {
+            Ok(payload) => {
+                let status = if payload.is_some() {
+                    Status::Complete
+                } else {
+                    Status::Accepted
+                };
+                (Ack { seq, status }, payload)
+            }
+            Err(status) => (Ack { seq, status }, None),
+        }
+    }
+
+    fn start(&mut self, data: &[u8]) -> Result<Option<Payload>, Status> {
+        self.reset();
+        if data.len() < FIRST_HEADER_SIZE {
+            return Err(Status::Malformed);
+        }
+        let len = u16::from_le_bytes([data[0], data[1]]) as usize;
+        if len > MAX_PAYLOAD_SIZE {
+            return Err(Status::Overflow);
+        }
+        self.expected_len = len;
+        self.crc = u16::from_le_bytes([data[2], data[3]]);
+        self.next_seq = Some(0);
+        self.append(&data[FIRST_HEADER_SIZE..])
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<Option<Payload>, Status> {
+        if self.buf.len() + data.len() > self.expected_len
+            || self.buf.extend_from_slice(data).is_err()
+        {
+            self.reset();
+            return Err(Status::Overflow);
+        }
+
+        if self.buf.len() < self.expected_len {
+            self.next_seq = self.next_seq.and_then(|s| s.checked_add(1));
+            return Ok(None);
+        }
+
+        let payload = core::mem::take(&mut self.buf);
+        let crc = self.crc;
+        self.reset();
+        if crc16(&payload) == crc {
+            Ok(Some(payload))
+        } else {
+            Err(Status::CrcMismatch)
+        }
+    }
+}
+
+/// CRC-16/CCITT-FALSE (polynomial `0x1021`, initial value `0xFFFF`)
+pub const fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    let mut i = 0;
+    while i < data.len() {
+        crc ^= (data[i] as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
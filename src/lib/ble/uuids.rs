@@ -0,0 +1,59 @@
+//! UUIDs of the Pomodoro GATT profile
+//!
+//! Every UUID is derived from [`BASE`] by putting a 16-bit id into bytes 2..4, the same way
+//! Bluetooth SIG UUIDs are derived from the Bluetooth base UUID. Byte arrays are little endian,
+//! the order used by the SoftDevice and in advertising data.
+
+/// Base UUID `93d30000-5a96-4abd-98e2-8207796ef8c8`
+pub const BASE: [u8; 16] = [
+    0xc8, 0xf8, 0x6e, 0x79, 0x07, 0x82, 0xe2, 0x98, 0xbd, 0x4a, 0x96, 0x5a, 0x00, 0x00, 0xd3, 0x93,
+];
+
+/// Configuration service
+pub const CONFIG_SERVICE: [u8; 16] = from_base(0x0100);
+/// Configuration service, as a string
+pub const CONFIG_SERVICE_STR: &str = "93d30100-5a96-4abd-98e2-8207796ef8c8";
+
+/// Config characteristic: `"work,rest"` string written in a single write
+pub const CONFIG_CHAR: [u8; 16] = from_base(0x0101);
+/// Config characteristic, as a string
+pub const CONFIG_CHAR_STR: &str = "93d30101-5a96-4abd-98e2-8207796ef8c8";
+
+/// Transfer characteristic: frames of a chunked transfer
+pub const TRANSFER_CHAR: [u8; 16] = from_base(0x0102);
+/// Transfer characteristic, as a string
+pub const TRANSFER_CHAR_STR: &str = "93d30102-5a96-4abd-98e2-8207796ef8c8";
+
+/// Transfer acknowledgement characteristic: notifies `[seq, status]` for each frame
+pub const TRANSFER_ACK_CHAR: [u8; 16] = from_base(0x0103);
+/// Transfer acknowledgement characteristic, as a string
+pub const TRANSFER_ACK_CHAR_STR: &str = "93d30103-5a96-4abd-98e2-8207796ef8c8";
+
+/// Config result characteristic: error code and message of the last config request
+pub const CONFIG_RESULT_CHAR: [u8; 16] = from_base(0x0104);
+/// Config result characteristic, as a string
+pub const CONFIG_RESULT_CHAR_STR: &str = "93d30104-5a96-4abd-98e2-8207796ef8c8";
+
+/// Ringtone characteristic: frames of a chunked transfer of a sound's slot and RTTTL text
+pub const RINGTONE_CHAR: [u8; 16] = from_base(0x0105);
+/// Ringtone characteristic, as a string
+pub const RINGTONE_CHAR_STR: &str = "93d30105-5a96-4abd-98e2-8207796ef8c8";
+
+/// Volume characteristic: speaker volume level from 0 (muted) to 3
+pub const VOLUME_CHAR: [u8; 16] = from_base(0x0106);
+/// Volume characteristic, as a string
+pub const VOLUME_CHAR_STR: &str = "93d30106-5a96-4abd-98e2-8207796ef8c8";
+
+/// Tick characteristic: seconds between ticks during work, `0` for no ticks
+pub const TICK_CHAR: [u8; 16] = from_base(0x0107);
+/// Tick characteristic, as a string
+pub const TICK_CHAR_STR: &str = "93d30107-5a96-4abd-98e2-8207796ef8c8";
+
+/// Derives a UUID from [`BASE`] and a 16-bit id
+pub const fn from_base(id: u16) -> [u8; 16] {
+    let mut uuid = BASE;
+    let id = id.to_le_bytes();
+    uuid[12] = id[0];
+    uuid[13] = id[1];
+    uuid
+}
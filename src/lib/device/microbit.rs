@@ -1,22 +1,28 @@
 //! Board definition for Microbit v2
-use crate::display::LedMatrix as LedMatrixDriver;
+use crate::{
+    display::{LedMatrix as LedMatrixDriver, SensePin},
+    sound::SoundPlayer,
+};
 use embassy_nrf::{
-    gpio::{AnyPin, Input, Level, Output, OutputDrive, Pin, Pull},
+    gpio::{AnyPin, Flex, Input, Level, Output, OutputDrive, Pin, Pull},
     interrupt::{self, InterruptExt, Priority},
     peripherals::{PWM0, TWISPI0, UARTE0},
     pwm::{Prescaler, SimplePwm},
+    saadc::{self, ChannelConfig, Gain, Reference, Saadc},
     twim::Twim,
     uarte::{self, Uarte},
 };
 
-/// LED matrix peripheral for the micro:bit
-pub type LedMatrix = LedMatrixDriver<Output<'static, AnyPin>, 5, 5>;
+/// LED matrix peripheral for the micro:bit. The pins can be read back to sense light.
+pub type LedMatrix = LedMatrixDriver<Flex<'static, AnyPin>, 5, 5>;
 
 pub struct Board {
     /// Onboard Button A
     pub button1: Input<'static, AnyPin>,
     /// Onboard Button B
     pub button2: Input<'static, AnyPin>,
+    /// Capacitive touch logo
+    pub logo: Flex<'static, AnyPin>,
     /// TWI
     pub twim: Twim<'static, TWISPI0>,
     /// UART: Serial - USB
@@ -25,6 +31,10 @@ pub struct Board {
     pub display: LedMatrix,
     /// PWM pin
     pub pwm: SimplePwm<'static, PWM0>,
+    /// Microphone
+    pub mic: Saadc<'static, 1>,
+    /// Microphone power, on while held
+    pub mic_power: Output<'static, AnyPin>,
 }
 
 impl Board {
@@ -32,19 +42,19 @@ impl Board {
     pub fn init(p: embassy_nrf::Peripherals) -> Board {
         // 5x5 LED display
         let rows = [
-            output_pin(p.P0_21.degrade()),
-            output_pin(p.P0_22.degrade()),
-            output_pin(p.P0_15.degrade()),
-            output_pin(p.P0_24.degrade()),
-            output_pin(p.P0_19.degrade()),
+            matrix_pin(p.P0_21.degrade()),
+            matrix_pin(p.P0_22.degrade()),
+            matrix_pin(p.P0_15.degrade()),
+            matrix_pin(p.P0_24.degrade()),
+            matrix_pin(p.P0_19.degrade()),
         ];
 
         let cols = [
-            output_pin(p.P0_28.degrade()),
-            output_pin(p.P0_11.degrade()),
-            output_pin(p.P0_31.degrade()),
-            output_pin(p.P1_05.degrade()),
-            output_pin(p.P0_30.degrade()),
+            matrix_pin(p.P0_28.degrade()),
+            matrix_pin(p.P0_11.degrade()),
+            matrix_pin(p.P0_31.degrade()),
+            matrix_pin(p.P1_05.degrade()),
+            matrix_pin(p.P0_30.degrade()),
         ];
         let display = LedMatrixDriver::new(rows, cols);
 
@@ -52,6 +62,9 @@ impl Board {
         let button1 = Input::new(p.P0_14.degrade(), Pull::Up);
         let button2 = Input::new(p.P0_23.degrade(), Pull::Up);
 
+        // Touch logo, sensed by timing how long the pad takes to charge
+        let logo = Flex::new(p.P1_04.degrade());
+
         // TWI
         let twim_config = embassy_nrf::twim::Config::default();
         let twim_irq = interrupt::take!(SPIM0_SPIS0_TWIM0_TWIS0_SPI0_TWI0);
@@ -71,17 +84,58 @@ impl Board {
         pwm.set_prescaler(Prescaler::Div1);
         pwm.set_max_duty(32767);
 
+        // Microphone, powered from P0_20. The input covers the full supply range, as the
+        // microphone output sits at a bias voltage.
+        let mic_power = Output::new(p.P0_20.degrade(), Level::High, OutputDrive::HighDrive);
+        let mut mic_config = ChannelConfig::single_ended(p.P0_05);
+        mic_config.gain = Gain::GAIN1_4;
+        mic_config.reference = Reference::VDD1_4;
+        let saadc_irq = interrupt::take!(SAADC);
+        saadc_irq.set_priority(Priority::P3);
+        let mic = Saadc::new(p.SAADC, saadc_irq, saadc::Config::default(), [mic_config]);
+
         Board {
             display,
             button1,
             button2,
+            logo,
             twim,
             uart,
             pwm,
+            mic,
+            mic_power,
         }
     }
 }
 
-fn output_pin(pin: AnyPin) -> Output<'static, AnyPin> {
-    Output::new(pin, Level::Low, OutputDrive::Standard)
+fn matrix_pin(pin: AnyPin) -> Flex<'static, AnyPin> {
+    let mut pin = Flex::new(pin);
+    pin.set_low();
+    pin.set_as_output(OutputDrive::Standard);
+    pin
+}
+
+impl SensePin for Flex<'static, AnyPin> {
+    fn set_as_input(&mut self) {
+        Flex::set_as_input(self, Pull::None);
+    }
+
+    fn set_as_output(&mut self) {
+        Flex::set_as_output(self, OutputDrive::Standard);
+    }
+
+    fn is_high(&self) -> bool {
+        Flex::is_high(self)
+    }
+}
+
+impl SoundPlayer for SimplePwm<'static, PWM0> {
+    fn tone(&mut self, freq: u16, duty: u16) {
+        self.set_period(freq as u32);
+        self.set_duty(0, duty);
+    }
+
+    fn silence(&mut self) {
+        self.set_duty(0, 0);
+    }
 }
@@ -1,2 +1,2 @@
 mod microbit;
-pub use microbit::Board;
+pub use microbit::{Board, LedMatrix};
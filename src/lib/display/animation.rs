@@ -0,0 +1,169 @@
+//! Animations and transitions
+//!
+//! An [`Animation`] is a sequence of [`Keyframe`]s that plays once or loops. A [`Transition`]
+//! turns one frame into another in a few steps. Both are played as a [`Clip`], which the
+//! display task shows over the posted frames until it ends or is cancelled, see
+//! [`play`](super::play).
+use super::{Frame, GrayFrame, Image, MAX_LEVEL};
+use embassy_time::Duration;
+
+/// Steps of a dissolve
+const DISSOLVE_STEPS: usize = 8;
+
+/// A frame of an animation and how long it shows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe<const COLS: usize, const ROWS: usize> {
+    pub image: Image<COLS, ROWS>,
+    pub duration: Duration,
+}
+
+/// A sequence of frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Animation<'a, const COLS: usize, const ROWS: usize> {
+    pub frames: &'a [Keyframe<COLS, ROWS>],
+    /// Start over after the last frame instead of ending
+    pub looping: bool,
+}
+
+impl<const COLS: usize, const ROWS: usize> Animation<'_, COLS, ROWS> {
+    /// Image at `elapsed` since the start and how long it still shows, or `None` once an
+    /// animation that doesn't loop is over
+    pub fn frame_at(&self, elapsed: Duration) -> Option<(Image<COLS, ROWS>, Duration)> {
+        let total: u64 = self.frames.iter().map(|f| f.duration.as_ticks()).sum();
+        let mut ticks = elapsed.as_ticks();
+        if self.looping && total > 0 {
+            ticks %= total;
+        }
+        for frame in self.frames {
+            let duration = frame.duration.as_ticks();
+            if ticks < duration {
+                return Some((frame.image, Duration::from_ticks(duration - ticks)));
+            }
+            ticks -= duration;
+        }
+        None
+    }
+}
+
+/// Effect for changing from one frame to another
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Transition {
+    /// The new frame pushes the old one out to the left
+    SlideLeft,
+    /// The new frame pushes the old one out to the right
+    SlideRight,
+    /// The new frame is uncovered from left to right
+    Wipe,
+    /// Pixels change over in a scattered order
+    Dissolve,
+    /// The old frame fades out, then the new one fades in
+    Fade,
+}
+
+impl Transition {
+    /// Number of steps for a frame of a width
+    pub fn steps(&self, cols: usize) -> usize {
+        match self {
+            Self::SlideLeft | Self::SlideRight | Self::Wipe => cols,
+            Self::Dissolve => DISSOLVE_STEPS,
+            Self::Fade => 2 * MAX_LEVEL as usize,
+        }
+    }
+
+    /// Image at `step` of [`steps`](Self::steps), counting from 1. The last step shows `to`.
+    pub fn frame<const COLS: usize, const ROWS: usize>(
+        &self,
+        from: &Frame<COLS, ROWS>,
+        to: &Frame<COLS, ROWS>,
+        step: usize,
+    ) -> Image<COLS, ROWS> {
+        let steps = self.steps(COLS);
+        let step = step.min(steps);
+        let (mut old, mut new) = (*from, *to);
+        match self {
+            Self::SlideLeft => {
+                old.shift_left(step);
+                new.shift_right(COLS - step);
+            }
+            Self::SlideRight => {
+                old.shift_right(step);
+                new.shift_left(COLS - step);
+            }
+            Self::Wipe => {
+                // Columns before `step` show the new frame
+                let mut covered = Frame::empty();
+                covered.fill_rect(0, 0, step as isize, ROWS as isize);
+                new.and(&covered);
+                covered.invert();
+                old.and(&covered);
+            }
+            Self::Dissolve => {
+                let pixels = COLS * ROWS;
+                let changed = step * pixels / steps;
+                for y in 0..ROWS {
+                    for x in 0..COLS {
+                        // A fixed scattered order of the pixels
+                        let i = (y * COLS + x) * 37 + 11;
+                        if i % pixels < changed {
+                            old.unset(x, y);
+                        } else {
+                            new.unset(x, y);
+                        }
+                    }
+                }
+            }
+            Self::Fade => {
+                let max = MAX_LEVEL as usize;
+                return Image::Gray(match step <= max {
+                    true => GrayFrame::from_frame(from, (max - step) as u8),
+                    false => GrayFrame::from_frame(to, (step - max) as u8),
+                });
+            }
+        }
+        old.or(&new);
+        Image::Binary(old)
+    }
+}
+
+/// Something to play on the display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clip<'a, const COLS: usize, const ROWS: usize> {
+    Animation(Animation<'a, COLS, ROWS>),
+    /// A transition played over a duration
+    Transition {
+        from: Frame<COLS, ROWS>,
+        to: Frame<COLS, ROWS>,
+        effect: Transition,
+        duration: Duration,
+    },
+}
+
+impl<const COLS: usize, const ROWS: usize> Clip<'_, COLS, ROWS> {
+    /// Image at `elapsed` since the start and how long it still shows, or `None` once the clip
+    /// is over
+    pub fn frame_at(&self, elapsed: Duration) -> Option<(Image<COLS, ROWS>, Duration)> {
+        match self {
+            Self::Animation(animation) => animation.frame_at(elapsed),
+            Self::Transition {
+                from,
+                to,
+                effect,
+                duration,
+            } => {
+                if elapsed >= *duration {
+                    return None;
+                }
+                // Steps split the duration evenly, the last one takes what is left over
+                let steps = effect.steps(COLS) as u64;
+                let step_ticks = (duration.as_ticks() / steps).max(1);
+                let step = (elapsed.as_ticks() / step_ticks).min(steps - 1);
+                let step_end = match step + 1 == steps {
+                    true => duration.as_ticks(),
+                    false => (step + 1) * step_ticks,
+                };
+                let image = effect.frame(from, to, step as usize + 1);
+                Some((image, Duration::from_ticks(step_end - elapsed.as_ticks())))
+            }
+        }
+    }
+}
@@ -40,6 +40,38 @@ use super::*;
         0b00100,
     ]);
 
+/// Light a share of the 25 LEDs proportional to `value / max`, rounded up, in reading order
+pub fn fill_5x5(value: u32, max: u32) -> Frame<5, 5> {
+    let lit = match max {
+        0 => 0,
+        _ => ((value.min(max) as u64 * 25 + max as u64 - 1) / max as u64) as usize,
+    };
+    let mut frame = Frame::empty();
+    for i in 0..lit {
+        frame.set(i % 5, i / 5);
+    }
+    frame
+}
+
+/// Like [`fill_5x5`], with the last LED lit at a level for the share of its 1/25. Levels are
+/// rounded up, so any value above 0 lights something.
+pub fn fill_5x5_gray(value: u32, max: u32) -> GrayFrame<5, 5> {
+    let (levels, top) = (25 * MAX_LEVEL as u64, MAX_LEVEL as u64);
+    let lit = match max {
+        0 => 0,
+        _ => (value.min(max) as u64 * levels + max as u64 - 1) / max as u64,
+    };
+    let (full, rest) = ((lit / top) as usize, (lit % top) as u8);
+    let mut frame = GrayFrame::empty();
+    for i in 0..full {
+        frame.set(i % 5, i / 5, MAX_LEVEL);
+    }
+    if rest > 0 {
+        frame.set(full % 5, full / 5, rest);
+    }
+    frame
+}
+
 /// Construct a 5x5 frame from a byte slice
 pub const fn frame_5x5<const XSIZE: usize, const YSIZE: usize>(
     input: &[u8; 5],
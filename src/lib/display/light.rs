@@ -0,0 +1,93 @@
+//! Ambient light sensing with the LED matrix
+//!
+//! A reverse-biased LED works as a small photodiode: its capacitance discharges faster in
+//! brighter light. [`LedMatrix::sense_light`](super::LedMatrix::sense_light) times the
+//! discharge and [`AutoBrightness`] turns the times into a display brightness.
+use super::Brightness;
+
+/// A matrix pin that can be read back, for sensing light with the LEDs
+pub trait SensePin {
+    /// Stops driving the pin so it floats and can be read
+    fn set_as_input(&mut self);
+    /// Drives the pin again
+    fn set_as_output(&mut self);
+    /// Whether the pin reads high
+    fn is_high(&self) -> bool;
+}
+
+/// Discharge times, in polling loops, mapped to the ends of the brightness range
+#[derive(Clone, Copy, Debug)]
+pub struct LightConfig {
+    /// Discharge time in bright light, and below, for [`Brightness::MAX`]
+    pub bright: u32,
+    /// Discharge time in the dark, and above, for [`Brightness::MIN`]
+    pub dark: u32,
+}
+
+impl Default for LightConfig {
+    fn default() -> Self {
+        LightConfig {
+            bright: 2,
+            dark: 512,
+        }
+    }
+}
+
+/// Maps discharge times to a brightness
+///
+/// Light levels span several orders of magnitude, so the brightness follows the logarithm of
+/// the discharge time. It is smoothed, and only changes once the light has moved by more than
+/// half a brightness step, so passing shadows and a level at the edge between two steps don't
+/// make the display flicker.
+pub struct AutoBrightness {
+    config: LightConfig,
+    /// Smoothed log2 of the discharge time in 1/256ths
+    smoothed: Option<i32>,
+    level: u8,
+}
+
+impl AutoBrightness {
+    /// Weight of the previous measurements: each new one moves the smoothed value by 1/4
+    const SMOOTHING: i32 = 4;
+    /// How far, in 1/256ths of a step, the light has to move before the brightness changes
+    const HYSTERESIS: i32 = 160;
+
+    pub fn new(config: LightConfig) -> Self {
+        AutoBrightness {
+            config,
+            smoothed: None,
+            level: Brightness::MAX.level(),
+        }
+    }
+
+    /// Feeds a discharge time. Returns the brightness to use.
+    pub fn update(&mut self, discharge: u32) -> Brightness {
+        let (bright, dark) = (log2(self.config.bright), log2(self.config.dark));
+        let x = log2(discharge).clamp(bright, dark);
+        let smoothed = match self.smoothed {
+            Some(s) => s + (x - s) / Self::SMOOTHING,
+            None => x,
+        };
+
+        // Brightness level in 1/256ths
+        let max = Brightness::MAX.level() as i32;
+        let target = (dark - smoothed) * max * 256 / (dark - bright).max(1);
+        if self.smoothed.is_none() || (target - self.level as i32 * 256).abs() > Self::HYSTERESIS {
+            self.level = ((target + 128) / 256) as u8;
+        }
+        self.smoothed = Some(smoothed);
+        Brightness::new(self.level)
+    }
+}
+
+/// log2 of `x` in 1/256ths, linear between powers of two
+fn log2(x: u32) -> i32 {
+    let x = x.max(1);
+    let int = 31 - x.leading_zeros();
+    let frac = if int >= 8 {
+        x >> (int - 8)
+    } else {
+        x << (8 - int)
+    };
+    ((int << 8) | (frac & 0xFF)) as i32
+}
@@ -2,31 +2,74 @@
 //!
 //! * Can display 5x5 bitmaps from raw data or characters
 //! * Methods for scrolling text across LED matrix or displaying a bitmap for a duration
+//! * Grayscale frames are shown with bit-angle modulation over the row scan, see [`ScanMode`]
+//! * Frames are rotated by a [`Rotation`], which [`display_task`] keeps in line with
+//!   [`DISPLAY_ROTATION`](crate::types::DISPLAY_ROTATION)
+//! * The LEDs double as a light sensor for [`AutoBrightness`]
+//! * [`display_task`] scans the display all the time, and the app only posts frames
+//! * Animations and transitions play over the posted frames, see [`Clip`]
 use embassy_time::{block_for, Duration, Instant, Timer};
 use embedded_hal::digital::OutputPin;
 
 pub mod bitmaps;
 
+mod animation;
+mod light;
+mod task;
 mod types;
+pub use animation::*;
+pub use light::*;
+pub use task::*;
 pub use types::*;
 
 const REFRESH_INTERVAL: Duration = Duration::from_micros(500);
+/// Time the lowest bit plane of a gray frame is shown for. Each higher plane is shown twice as
+/// long as the one below.
+const BAM_TICK: Duration = Duration::from_micros(125);
+/// Time for the reverse-biased LEDs to charge before sensing light
+const CHARGE_TIME: Duration = Duration::from_micros(20);
+/// Upper bound of a light measurement, in polling loops. Reached in the dark. The measurement
+/// blocks the executor, so it is kept well under a row period.
+const MAX_DISCHARGE: u32 = 1024;
 
-/// Led matrix driver supporting arbitrary sized led matrixes.
-///
-/// NOTE: Currently restricted by 8 bits width
-pub struct LedMatrix<P, const ROWS: usize, const COLS: usize>
+/// A frame for the driver, binary or gray
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Image<const COLS: usize, const ROWS: usize, const WORDS: usize = 1> {
+    Binary(Frame<COLS, ROWS, WORDS>),
+    Gray(GrayFrame<COLS, ROWS, WORDS>),
+}
+
+/// How the driver scans the display
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum ScanMode {
+    /// On/off pixels of a [`Frame`], one step per row
+    Binary,
+    /// Levels of a [`GrayFrame`], one step per bit plane of each row. Plane n is held for
+    /// `BAM_TICK << n`, so a pixel is lit for a time proportional to its level.
+    Gray,
+}
+
+/// Led matrix driver supporting arbitrary sized led matrixes. Rows of more than 8 columns
+/// need more `WORDS`, see [`Bitmap::CAPACITY`].
+pub struct LedMatrix<P, const ROWS: usize, const COLS: usize, const WORDS: usize = 1>
 where
     P: OutputPin + 'static,
 {
     pin_rows: [P; ROWS],
     pin_cols: [P; COLS],
-    frame_buffer: Frame<COLS, ROWS>,
+    frame_buffer: Frame<COLS, ROWS, WORDS>,
+    gray_buffer: GrayFrame<COLS, ROWS, WORDS>,
+    mode: ScanMode,
     row_p: usize,
+    /// Bit plane of the current row in gray mode
+    plane_p: usize,
+    /// Whether the last row has been turned off
+    blanked: bool,
     brightness: Brightness,
+    rotation: Rotation,
 }
 
-impl<P, const ROWS: usize, const COLS: usize> LedMatrix<P, ROWS, COLS>
+impl<P, const ROWS: usize, const COLS: usize, const WORDS: usize> LedMatrix<P, ROWS, COLS, WORDS>
 where
     P: OutputPin,
 {
@@ -36,14 +79,20 @@ where
             pin_rows,
             pin_cols,
             frame_buffer: Frame::empty(),
+            gray_buffer: GrayFrame::empty(),
+            mode: ScanMode::Binary,
             row_p: 0,
+            plane_p: 0,
+            blanked: false,
             brightness: Default::default(),
+            rotation: Rotation::R0,
         }
     }
 
     /// Clear all LEDs
     pub fn clear(&mut self) {
         self.frame_buffer.clear();
+        self.gray_buffer.clear();
         for row in self.pin_rows.iter_mut() {
             row.set_high().ok();
         }
@@ -63,9 +112,42 @@ where
         self.frame_buffer.unset(x, y);
     }
 
-    /// Apply the provided frame onto the frame buffer
-    pub fn apply(&mut self, frame: Frame<COLS, ROWS>) {
+    /// Apply the provided frame onto the frame buffer, and scan it in binary mode
+    pub fn apply(&mut self, frame: Frame<COLS, ROWS, WORDS>) {
         self.frame_buffer = frame;
+        self.set_mode(ScanMode::Binary);
+    }
+
+    /// Apply the provided gray frame onto the gray frame buffer, and scan it in gray mode
+    pub fn apply_gray(&mut self, frame: GrayFrame<COLS, ROWS, WORDS>) {
+        self.gray_buffer = frame;
+        self.set_mode(ScanMode::Gray);
+    }
+
+    /// Apply a binary or gray image, rotated by the [`rotation`](Self::rotation)
+    pub fn show(&mut self, image: Image<COLS, ROWS, WORDS>) {
+        match image {
+            Image::Binary(mut frame) => {
+                frame.rotate(self.rotation);
+                self.apply(frame);
+            }
+            Image::Gray(mut frame) => {
+                frame.rotate(self.rotation);
+                self.apply_gray(frame);
+            }
+        }
+    }
+
+    /// Return the scan mode
+    pub fn mode(&self) -> ScanMode {
+        self.mode
+    }
+
+    fn set_mode(&mut self, mode: ScanMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.plane_p = 0;
+        }
     }
 
     /// Adjust the brightness level
@@ -73,6 +155,21 @@ where
         self.brightness = brightness;
     }
 
+    /// Return the brightness level
+    pub fn brightness(&self) -> Brightness {
+        self.brightness
+    }
+
+    /// Set the rotation applied to frames passed to [`display`](Self::display)
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Return the rotation
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
     /// Increase brightness relative to current setting
     pub fn increase_brightness(&mut self) {
         self.brightness += 1;
@@ -83,40 +180,116 @@ where
         self.brightness -= 1;
     }
 
-    /// Perform a full refresh of the display based on the current frame buffer
-    pub fn render(&mut self) {
-        for row in self.pin_rows.iter_mut() {
-            row.set_low().ok();
+    /// Perform a refresh step of the display based on the current frame buffer, and return
+    /// how long to wait before the next step. Each row starts with the display dark for a time
+    /// that sets the brightness, then the row is lit.
+    pub fn render(&mut self) -> Duration {
+        if self.plane_p == 0 && !self.blanked {
+            for row in self.pin_rows.iter_mut() {
+                row.set_low().ok();
+            }
+            self.blanked = true;
+            let dark = self.dark_time();
+            if dark > Duration::from_ticks(0) {
+                return dark;
+            }
         }
+        self.blanked = false;
 
-        for (cid, col) in self.pin_cols.iter_mut().enumerate() {
-            if self.frame_buffer.is_set(cid, self.row_p) {
-                col.set_low().ok();
-            } else {
-                col.set_high().ok();
+        let frame = match self.mode {
+            ScanMode::Binary => &self.frame_buffer,
+            ScanMode::Gray => self.gray_buffer.plane(self.plane_p),
+        };
+        set_columns(&mut self.pin_cols, frame, self.row_p);
+        self.pin_rows[self.row_p].set_high().ok();
+
+        let hold = match self.mode {
+            ScanMode::Binary => REFRESH_INTERVAL,
+            ScanMode::Gray => {
+                let hold = BAM_TICK * (1 << self.plane_p);
+                self.plane_p = (self.plane_p + 1) % GRAY_BITS;
+                hold
             }
+        };
+        if self.plane_p == 0 {
+            self.row_p = (self.row_p + 1) % self.pin_rows.len();
         }
+        hold
+    }
 
-        // Adjust interval will impact brightness of the LEDs
-        block_for(Duration::from_micros(
+    /// Time the display stays dark before each row. Adjusting it changes the brightness.
+    fn dark_time(&self) -> Duration {
+        Duration::from_micros(
             ((Brightness::MAX.level() - self.brightness.level()) as u64) * 6000
                 / Brightness::MAX.level() as u64,
-        ));
+        )
+    }
 
-        self.pin_rows[self.row_p].set_high().ok();
+    /// Measure ambient light: reverse-bias the LEDs and count polling loops until the columns
+    /// discharge. Smaller counts mean brighter light. The display is blank meanwhile.
+    pub fn sense_light(&mut self) -> u32
+    where
+        P: SensePin,
+    {
+        // Rows drive the anodes and columns the cathodes
+        for row in self.pin_rows.iter_mut() {
+            row.set_low().ok();
+        }
+        for col in self.pin_cols.iter_mut() {
+            col.set_high().ok();
+        }
+        block_for(CHARGE_TIME);
+
+        for col in self.pin_cols.iter_mut() {
+            col.set_as_input();
+        }
+        let mut count = 0;
+        while count < MAX_DISCHARGE && self.pin_cols.iter().any(|col| col.is_high()) {
+            count += 1;
+        }
 
-        self.row_p = (self.row_p + 1) % self.pin_rows.len();
+        for col in self.pin_cols.iter_mut() {
+            col.set_as_output();
+            col.set_high().ok();
+        }
+        count
     }
 
     /// Display the provided frame for the duration. Handles screen refresh
     /// in an async display loop.
-    pub async fn display(&mut self, frame: Frame<COLS, ROWS>, length: Duration) {
-        self.apply(frame);
+    pub async fn display(&mut self, frame: Frame<COLS, ROWS, WORDS>, length: Duration) {
+        self.scan(Image::Binary(frame), length).await;
+    }
+
+    /// Display the provided gray frame for the duration in gray mode, see
+    /// [`display`](Self::display)
+    pub async fn display_gray(&mut self, frame: GrayFrame<COLS, ROWS, WORDS>, length: Duration) {
+        self.scan(Image::Gray(frame), length).await;
+    }
+
+    /// Show an image and refresh the display for the duration, then clear it
+    async fn scan(&mut self, image: Image<COLS, ROWS, WORDS>, length: Duration) {
+        self.show(image);
         let end = Instant::now() + length;
         while Instant::now() < end {
-            self.render();
-            Timer::after(REFRESH_INTERVAL).await;
+            let hold = self.render();
+            Timer::after(hold).await;
         }
         self.clear();
     }
 }
+
+/// Drive the columns for a row of a frame: low lights a pixel
+fn set_columns<P: OutputPin, const ROWS: usize, const COLS: usize, const WORDS: usize>(
+    pin_cols: &mut [P; COLS],
+    frame: &Frame<COLS, ROWS, WORDS>,
+    row: usize,
+) {
+    for (cid, col) in pin_cols.iter_mut().enumerate() {
+        if frame.is_set(cid, row) {
+            col.set_low().ok();
+        } else {
+            col.set_high().ok();
+        }
+    }
+}
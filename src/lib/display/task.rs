@@ -0,0 +1,138 @@
+//! Display task
+//!
+//! The task owns the LED matrix and scans it all the time. Other tasks post frames with
+//! [`show`] and [`show_gray`], and the task keeps scanning the latest one until the next
+//! arrives. A clip started with [`play`] is shown instead until it ends or is cancelled, then
+//! the latest posted frame shows again. The task follows [`DISPLAY_ROTATION`] and, unless a
+//! brightness is set, the ambient light.
+use super::{AutoBrightness, Brightness, Clip, Frame, GrayFrame, Image, LightConfig};
+use crate::{
+    device::LedMatrix,
+    types::{
+        ANIMATION_SIGNAL, BRIGHTNESS_SIGNAL, DISPLAY_BRIGHTNESS, DISPLAY_ROTATION, DISPLAY_SIGNAL,
+    },
+};
+use embassy_futures::select::{select4, Either4};
+use embassy_time::{Duration, Instant, Timer};
+
+/// How often the ambient light is measured
+const SENSE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Brightness of the display
+#[derive(Clone, Copy, Debug)]
+pub enum BrightnessSetting {
+    /// Follow the ambient light
+    Auto,
+    Fixed(Brightness),
+}
+
+/// Shows a frame until the next one is posted
+pub fn show(frame: Frame<5, 5>) {
+    DISPLAY_SIGNAL.signal(Image::Binary(frame));
+}
+
+/// Shows a gray frame until the next one is posted
+pub fn show_gray(frame: GrayFrame<5, 5>) {
+    DISPLAY_SIGNAL.signal(Image::Gray(frame));
+}
+
+/// Plays a clip over the posted frames. A clip already playing is replaced.
+pub fn play(clip: Clip<'static, 5, 5>) {
+    ANIMATION_SIGNAL.signal(Some(clip));
+}
+
+/// Stops the clip playing, if any, and shows the latest posted frame
+pub fn cancel_animation() {
+    ANIMATION_SIGNAL.signal(None);
+}
+
+/// Sets the brightness, or follows the ambient light again
+pub fn set_brightness(setting: BrightnessSetting) {
+    BRIGHTNESS_SIGNAL.signal(setting);
+}
+
+/// Display task
+#[embassy_executor::task]
+pub async fn display_task(mut matrix: LedMatrix) {
+    // Latest posted image, and the image on the display
+    let mut image = Image::Binary(Frame::empty());
+    let mut shown = image;
+    let mut playing: Option<(Clip<'static, 5, 5>, Instant)> = None;
+    let mut next_change = Instant::now();
+    let mut auto_brightness = Some(AutoBrightness::new(LightConfig::default()));
+    let mut next_sense = Instant::now();
+    loop {
+        if let Some(auto) = auto_brightness.as_mut() {
+            if Instant::now() >= next_sense {
+                let brightness = auto.update(matrix.sense_light());
+                matrix.set_brightness(brightness);
+                DISPLAY_BRIGHTNESS.lock(|b| b.set(brightness));
+                next_sense = Instant::now() + SENSE_INTERVAL;
+            }
+        }
+
+        let rotation = DISPLAY_ROTATION.lock(|r| r.get());
+        if rotation != matrix.rotation() {
+            matrix.set_rotation(rotation);
+            matrix.show(shown);
+        }
+
+        if let Some((clip, start)) = playing {
+            let now = Instant::now();
+            if now >= next_change {
+                match clip.frame_at(now - start) {
+                    Some((frame, remaining)) => {
+                        next_change = now + remaining;
+                        if frame != shown {
+                            shown = frame;
+                            matrix.show(shown);
+                        }
+                    }
+                    None => {
+                        playing = None;
+                        shown = image;
+                        matrix.show(shown);
+                    }
+                }
+            }
+        }
+
+        let hold = matrix.render();
+        match select4(
+            Timer::after(hold),
+            DISPLAY_SIGNAL.wait(),
+            ANIMATION_SIGNAL.wait(),
+            BRIGHTNESS_SIGNAL.wait(),
+        )
+        .await
+        {
+            Either4::First(_) => (),
+            Either4::Second(posted) => {
+                image = posted;
+                if playing.is_none() {
+                    shown = image;
+                    matrix.show(shown);
+                }
+            }
+            Either4::Third(Some(clip)) => {
+                playing = Some((clip, Instant::now()));
+                next_change = Instant::now();
+            }
+            Either4::Third(None) => {
+                if playing.take().is_some() {
+                    shown = image;
+                    matrix.show(shown);
+                }
+            }
+            Either4::Fourth(BrightnessSetting::Fixed(brightness)) => {
+                auto_brightness = None;
+                matrix.set_brightness(brightness);
+                DISPLAY_BRIGHTNESS.lock(|b| b.set(brightness));
+            }
+            Either4::Fourth(BrightnessSetting::Auto) => {
+                auto_brightness = Some(AutoBrightness::new(LightConfig::default()));
+                next_sense = Instant::now();
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Machine-readable event stream
+//!
+//! Important transitions are emitted as newline-delimited JSON on the serial port, e.g.
+//!
+//! ```text
+//! {"ts":1532,"event":{"type":"phase_started","phase":"running","duration":1500}}
+//! ```
+//!
+//! `ts` is the time since boot in milliseconds.
+use crate::types::{State, TimerConfig};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::Instant;
+use serde::Serialize;
+
+/// Maximum size of a serialized event line
+pub const LINE_SIZE: usize = 160;
+
+/// Events waiting to be written to the serial port
+pub static EVENTS: Channel<ThreadModeRawMutex, Record, 8> = Channel::new();
+
+/// Where a timer config came from
+#[derive(Clone, Copy, Debug, Serialize, defmt::Format)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    Flash,
+    Default,
+    Ble,
+    Shell,
+    /// Settings mode on the device
+    Device,
+}
+
+/// Onboard buttons and the touch logo
+#[derive(Clone, Copy, Debug, Serialize, defmt::Format)]
+#[serde(rename_all = "snake_case")]
+pub enum Button {
+    A,
+    B,
+    Logo,
+}
+
+/// An app event
+#[derive(Clone, Copy, Debug, Serialize, defmt::Format)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// The app has booted
+    Boot,
+    /// A timer config has been loaded
+    ConfigLoaded {
+        source: ConfigSource,
+        config: TimerConfig,
+    },
+    /// A config request was rejected
+    ConfigError { code: u8, message: &'static str },
+    /// A work or rest phase has started
+    PhaseStarted { phase: State, duration: u32 },
+    /// A work phase has been paused
+    Paused { remaining: u32 },
+    /// A work phase has been resumed
+    Resumed { remaining: u32 },
+    /// A work or rest phase has run to completion. Work phases report how long the room was
+    /// loud in seconds.
+    Completed {
+        phase: State,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        noisy_time: Option<u32>,
+    },
+    /// A button has been pressed
+    ButtonPressed { button: Button },
+    /// The speaker volume has changed. Level 0 is muted.
+    VolumeChanged { level: u8 },
+    /// Ticking during work has been turned on with an interval in seconds, or off with `0`
+    TickChanged { interval: u8 },
+    /// The alarm at the end of a phase has been acknowledged after ringing for `duration`
+    /// seconds
+    AlarmAcknowledged { duration: u32 },
+}
+
+/// An event with its timestamp
+#[derive(Clone, Copy, Debug, Serialize, defmt::Format)]
+pub struct Record {
+    /// Time since boot in milliseconds
+    pub ts: u64,
+    /// The event
+    pub event: Event,
+}
+
+impl Record {
+    /// Serializes the record as a JSON line terminated by `\n`
+    pub fn to_line(&self) -> Result<heapless::Vec<u8, LINE_SIZE>, serde_json_core::ser::Error> {
+        let mut buf = [0u8; LINE_SIZE];
+        let len = serde_json_core::to_slice(self, &mut buf[..LINE_SIZE - 1])?;
+        buf[len] = b'\n';
+        Ok(heapless::Vec::from_slice(&buf[..=len]).unwrap_or_default())
+    }
+}
+
+/// Emits an event. The event is dropped if the stream is backed up.
+pub fn emit(event: Event) {
+    let record = Record {
+        ts: Instant::now().as_millis(),
+        event,
+    };
+    if EVENTS.try_send(record).is_err() {
+        defmt::warn!("event dropped: {:?}", record);
+    }
+}
@@ -21,6 +21,11 @@ impl FlashStorage {
         f.read(self.start_addr, buf).await
     }
 
+    /// Erases the storage
+    pub async fn erase(&self, f: &mut Flash) -> Result<(), FlashError> {
+        f.erase(self.start_addr, self.end_addr).await
+    }
+
     /// Writes to flash
     pub async fn write(&self, f: &mut Flash, bytes: &mut [u8]) -> Result<(), FlashError> {
         f.erase(self.start_addr, self.end_addr).await?;
@@ -0,0 +1,2 @@
+//! Melodies for the speaker, see [`pomodoro_melody`]
+pub use pomodoro_melody::*;
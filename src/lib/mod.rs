@@ -3,10 +3,21 @@
 #![no_main]
 #![no_std]
 
+pub mod accel;
+pub mod alarm;
 pub mod ble;
 pub mod device;
 pub mod display;
+pub mod events;
 pub mod flash_storage;
+pub mod melody;
+pub mod noise;
+pub mod ringtones;
+pub mod rtttl;
+pub mod settings;
+pub mod shell;
+pub mod sound;
+pub mod touch;
 pub mod types;
 
 use defmt_rtt as _; // global logger
@@ -33,10 +44,1068 @@ pub fn exit() -> ! {
 #[cfg(test)]
 #[defmt_test::tests]
 mod unit_tests {
-    use defmt::assert;
+    use crate::{
+        accel::{FlipDetector, Lsm303agr, Orientation, RotationDetector, Sample, ADDRESS},
+        alarm::Alarm,
+        ble::{
+            advertising::{AdvEvent, AdvMode, AdvState, AdvertisingPolicy},
+            sync::{self, SyncBeacon},
+            transfer::{crc16, Ack, Reassembler, Status},
+            uuids,
+        },
+        display::{
+            bitmaps::*, Animation, AutoBrightness, Brightness, Clip, Frame, GrayFrame, Image,
+            Keyframe, LedMatrix, LightConfig, Rotation, ScanMode, SensePin, Transition, MAX_LEVEL,
+        },
+        events::{Event, Record},
+        ringtones::{self, Ringtones},
+        rtttl,
+        settings::{Field, Settings},
+        sound::{
+            SoundPlayer, SoundQueue, SoundRequest, SoundSettings, Speaker, Volume,
+            MAX_TICK_INTERVAL,
+        },
+        touch::{Touch, TouchDetector},
+        types::{
+            config_report, Buzzer, ConfigError, Payload, State, TimerConfig, MAX_PAYLOAD_SIZE,
+        },
+    };
+    use core::{cell::Cell, fmt::Write};
+    use defmt::{assert, assert_eq};
+    use embassy_time::{Duration, Instant};
+    use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, Operation};
+    use pomodoro_protocol::{self as protocol, FrameReader, Input, Request};
 
     #[test]
     fn it_works() {
         assert!(true)
     }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn transfer_reassembles_frames() {
+        let crc = crc16(b"25,5").to_le_bytes();
+        let mut r = Reassembler::new();
+
+        let (ack, payload) = r.push(&[0, 4, 0, crc[0], crc[1], b'2', b'5']);
+        assert_eq!(ack.status, Status::Accepted);
+        assert!(payload.is_none());
+
+        let (ack, payload) = r.push(&[1, b',', b'5']);
+        assert_eq!(ack.status, Status::Complete);
+        assert_eq!(payload.unwrap().as_slice(), b"25,5");
+    }
+
+    #[test]
+    fn transfer_rejects_out_of_sequence_frame() {
+        let mut r = Reassembler::new();
+        r.push(&[0, 4, 0, 0, 0, b'2', b'5']);
+        let (ack, _) = r.push(&[2, b',', b'5']);
+        assert_eq!(ack.status, Status::OutOfSequence);
+    }
+
+    #[test]
+    fn transfer_reports_overflow() {
+        let mut r = Reassembler::new();
+        // Announced larger than the receive buffer
+        let len = (MAX_PAYLOAD_SIZE as u16 + 1).to_le_bytes();
+        let (ack, _) = r.push(&[0, len[0], len[1], 0, 0]);
+        assert_eq!(ack.status, Status::Overflow);
+
+        // More data than announced, and the rest of the transfer is dropped
+        let (ack, _) = r.push(&[0, 2, 0, 0, 0, b'2', b'5', b',']);
+        assert_eq!(ack.status, Status::Overflow);
+        r.push(&[0, 4, 0, 0, 0, b'2', b'5']);
+        let (ack, _) = r.push(&[1, b',', b'5', b'0']);
+        assert_eq!(ack.status, Status::Overflow);
+        let (ack, _) = r.push(&[2, b'0']);
+        assert_eq!(ack.status, Status::OutOfSequence);
+    }
+
+    #[test]
+    fn transfer_reports_crc_mismatch_and_malformed_frames() {
+        let crc = crc16(b"25,5").to_le_bytes();
+        let mut r = Reassembler::new();
+        let (ack, payload) = r.push(&[0, 4, 0, crc[0], crc[1] ^ 1, b'2', b'5', b',', b'5']);
+        assert_eq!(
+            ack,
+            Ack {
+                seq: 0,
+                status: Status::CrcMismatch
+            }
+        );
+        assert!(payload.is_none());
+
+        let (ack, _) = r.push(&[]);
+        assert_eq!(ack.status, Status::Malformed);
+        let (ack, _) = r.push(&[0, 4, 0, crc[0]]);
+        assert_eq!(ack.status, Status::Malformed);
+    }
+
+    #[test]
+    fn transfer_recovers_after_errors() {
+        let crc = crc16(b"25,5").to_le_bytes();
+        let first = [0, 4, 0, crc[0], crc[1], b'2', b'5'];
+        let mut r = Reassembler::new();
+        for broken in [
+            &[0, 0xFF, 0xFF, 0, 0][..],
+            &[0, 1],
+            &[3, b'x'],
+            &[0, 1, 0, 0, 0, b'x'],
+        ] {
+            r.push(broken);
+            assert_eq!(r.push(&first).0.status, Status::Accepted);
+            let (ack, payload) = r.push(&[1, b',', b'5']);
+            assert_eq!(ack.status, Status::Complete);
+            assert_eq!(payload.unwrap().as_slice(), b"25,5");
+        }
+
+        // A new first frame abandons a transfer in progress
+        r.push(&[0, 8, 0, 0, 0, b'x']);
+        r.push(&first);
+        assert_eq!(r.push(&[1, b',', b'5']).0.status, Status::Complete);
+    }
+
+    #[test]
+    fn advertising_slows_down_and_stops_while_running() {
+        let policy = AdvertisingPolicy {
+            stop_while_running: true,
+            ..Default::default()
+        };
+        let mut adv = AdvState::new(Instant::from_secs(0));
+        let early = Instant::from_secs(10);
+        let late = Instant::from_secs(60);
+        assert_eq!(policy.mode(&adv, early), AdvMode::Fast);
+        assert_eq!(policy.mode(&adv, late), AdvMode::Slow);
+        adv.apply(AdvEvent::StateChanged(State::Running), early);
+        assert_eq!(policy.mode(&adv, late), AdvMode::Off);
+        adv.apply(AdvEvent::ButtonPressed, late);
+        assert_eq!(
+            policy.mode(&adv, late + Duration::from_secs(60)),
+            AdvMode::Slow
+        );
+        assert_eq!(policy.interval_units(AdvMode::Fast), 160);
+    }
+
+    #[test]
+    fn sync_beacon_round_trips_through_advertising_data() {
+        let beacon = SyncBeacon {
+            group: 7,
+            state: State::Resting,
+            remaining: 299,
+        };
+        let mut adv_data: heapless::Vec<u8, 31> = heapless::Vec::new();
+        adv_data.extend_from_slice(&[0x02, 0x01, 0x06]).unwrap();
+        adv_data.extend_from_slice(&beacon.to_ad()).unwrap();
+        assert_eq!(SyncBeacon::find(&adv_data, 7), Some(beacon));
+        // Leaders of other groups are ignored
+        assert_eq!(SyncBeacon::find(&adv_data, 0), None);
+        assert_eq!(SyncBeacon::find(&[0x02, 0x01, 0x06], 7), None);
+    }
+
+    #[test]
+    fn sync_beacon_aligns_follower() {
+        let beacon = SyncBeacon {
+            group: 0,
+            state: State::Running,
+            remaining: 600,
+        };
+        let age = Duration::from_secs(2);
+        assert_eq!(beacon.align(age, State::Running, 599), None);
+        assert_eq!(
+            beacon.align(age, State::Resting, 30),
+            Some((State::Running, 598))
+        );
+    }
+
+    #[test]
+    fn sync_group_survives_flash() {
+        assert_eq!(sync::group_from_bytes([0xFF; sync::GROUP_SIZE]), 0);
+        assert_eq!(sync::group_from_bytes(sync::group_to_bytes(255)), 255);
+        assert_eq!(sync::group_from_bytes(sync::group_to_bytes(7)), 7);
+    }
+
+    #[test]
+    fn config_parses_minutes() {
+        let config = TimerConfig::parse(&Payload::from_slice(b"50,10").unwrap()).unwrap();
+        assert_eq!(config.work_time, 50 * 60);
+        assert_eq!(config.rest_time, 10 * 60);
+    }
+
+    #[test]
+    fn config_reports_out_of_range_rest_time() {
+        let result = TimerConfig::parse(&Payload::from_slice(b"25,90").unwrap());
+        assert!(matches!(result, Err(ConfigError::RestOutOfRange(90))));
+
+        let report = config_report(result.as_ref().map(|_| ()));
+        assert_eq!(report[0], 5);
+        assert_eq!(&report[1..], b"rest time must be between 1 and 60 minutes");
+        assert_eq!(config_report(Ok(())).as_slice(), &[0]);
+    }
+
+    #[test]
+    fn config_errors_name_the_limits() {
+        let mut expected: heapless::String<64> = heapless::String::new();
+        let volume = Volume::MAX.level();
+        write!(expected, "volume must be between 0 and {}", volume).unwrap();
+        assert_eq!(
+            ConfigError::InvalidVolume(volume + 1).message(),
+            expected.as_str()
+        );
+
+        expected.clear();
+        let tick = MAX_TICK_INTERVAL;
+        write!(
+            expected,
+            "tick interval must be between 0 and {} seconds",
+            tick
+        )
+        .unwrap();
+        assert_eq!(
+            ConfigError::InvalidTick(tick + 1).message(),
+            expected.as_str()
+        );
+
+        // Times come from the protocol, which checks them against its ranges
+        let e = ConfigError::WorkOutOfRange(0);
+        assert_eq!(e.message(), protocol::Error::WorkOutOfRange.message());
+    }
+
+    #[test]
+    fn uuid_strings_match_bytes() {
+        for (bytes, string) in [
+            (uuids::CONFIG_SERVICE, uuids::CONFIG_SERVICE_STR),
+            (uuids::CONFIG_CHAR, uuids::CONFIG_CHAR_STR),
+            (uuids::TRANSFER_CHAR, uuids::TRANSFER_CHAR_STR),
+            (uuids::TRANSFER_ACK_CHAR, uuids::TRANSFER_ACK_CHAR_STR),
+            (uuids::CONFIG_RESULT_CHAR, uuids::CONFIG_RESULT_CHAR_STR),
+            (uuids::RINGTONE_CHAR, uuids::RINGTONE_CHAR_STR),
+            (uuids::VOLUME_CHAR, uuids::VOLUME_CHAR_STR),
+            (uuids::TICK_CHAR, uuids::TICK_CHAR_STR),
+        ] {
+            let mut s: heapless::String<36> = heapless::String::new();
+            for (i, b) in bytes.iter().rev().enumerate() {
+                if matches!(i, 4 | 6 | 8 | 10) {
+                    s.push('-').unwrap();
+                }
+                write!(s, "{:02x}", b).unwrap();
+            }
+            assert_eq!(s.as_str(), string);
+        }
+    }
+
+    #[test]
+    fn event_record_is_a_json_line() {
+        let record = Record {
+            ts: 1532,
+            event: Event::PhaseStarted {
+                phase: State::Running,
+                duration: 1500,
+            },
+        };
+        let line = record.to_line().unwrap();
+        assert_eq!(
+            &line[..],
+            b"{\"ts\":1532,\"event\":{\"type\":\"phase_started\",\"phase\":\"running\",\"duration\":1500}}\n"
+        );
+    }
+
+    #[test]
+    fn config_parses_protocol_frame() {
+        let request = Request::SetConfig(protocol::Config {
+            work_minutes: 50,
+            rest_minutes: 10,
+        });
+        let mut buf = [0u8; protocol::MAX_FRAME_SIZE];
+        let frame = protocol::encode(&request, &mut buf).unwrap();
+
+        let config = TimerConfig::parse(&Payload::from_slice(frame).unwrap())
+            .ok()
+            .unwrap();
+        assert_eq!(config.work_time, 50 * 60);
+        assert_eq!(config.rest_time, 10 * 60);
+    }
+
+    #[test]
+    fn frames_are_split_from_shell_input() {
+        let mut buf = [0u8; protocol::MAX_FRAME_SIZE];
+        let frame = protocol::encode(&Request::GetStatus, &mut buf).unwrap();
+        let mut reader: FrameReader<{ protocol::MAX_FRAME_SIZE }> = FrameReader::new();
+
+        assert_eq!(reader.push(b'h'), Input::Byte(b'h'));
+        let (last, rest) = frame.split_last().unwrap();
+        for b in rest {
+            assert_eq!(reader.push(*b), Input::Pending);
+        }
+        match reader.push(*last) {
+            Input::Frame(frame) => {
+                assert!(protocol::decode::<Request>(frame).ok() == Some(Request::GetStatus))
+            }
+            _ => defmt::panic!("frame not complete"),
+        }
+        assert_eq!(reader.push(b'\r'), Input::Byte(b'\r'));
+    }
+
+    /// I2C bus with a register file at the accelerometer's address
+    struct MockI2c {
+        regs: [u8; 0x40],
+    }
+
+    #[derive(Debug)]
+    struct MockError;
+
+    impl embedded_hal::i2c::Error for MockError {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    impl ErrorType for MockI2c {
+        type Error = MockError;
+    }
+
+    impl I2c for MockI2c {
+        fn read(&mut self, _: u8, _: &mut [u8]) -> Result<(), MockError> {
+            Err(MockError)
+        }
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), MockError> {
+            if address != ADDRESS {
+                return Err(MockError);
+            }
+            let start = (bytes[0] & 0x7F) as usize;
+            self.regs[start..start + bytes.len() - 1].copy_from_slice(&bytes[1..]);
+            Ok(())
+        }
+
+        fn write_iter<B: IntoIterator<Item = u8>>(&mut self, _: u8, _: B) -> Result<(), MockError> {
+            Err(MockError)
+        }
+
+        fn write_read(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), MockError> {
+            // Multi-byte reads need the auto-increment bit
+            if address != ADDRESS || (buffer.len() > 1 && bytes[0] & 0x80 == 0) {
+                return Err(MockError);
+            }
+            let start = (bytes[0] & 0x7F) as usize;
+            buffer.copy_from_slice(&self.regs[start..start + buffer.len()]);
+            Ok(())
+        }
+
+        fn write_iter_read<B: IntoIterator<Item = u8>>(
+            &mut self,
+            _: u8,
+            _: B,
+            _: &mut [u8],
+        ) -> Result<(), MockError> {
+            Err(MockError)
+        }
+
+        fn transaction<'a>(&mut self, _: u8, _: &mut [Operation<'a>]) -> Result<(), MockError> {
+            Err(MockError)
+        }
+
+        fn transaction_iter<'a, O: IntoIterator<Item = Operation<'a>>>(
+            &mut self,
+            _: u8,
+            _: O,
+        ) -> Result<(), MockError> {
+            Err(MockError)
+        }
+    }
+
+    #[test]
+    fn accelerometer_reads_board_frame() {
+        let mut regs = [0u8; 0x40];
+        regs[0x0F] = 0x33;
+        // Face up: the sensor on the back sees -1 g, at 2 mg per digit
+        for (i, v) in [8i16, -16, -500].iter().enumerate() {
+            regs[0x28 + 2 * i..0x2A + 2 * i].copy_from_slice(&(v << 4).to_le_bytes());
+        }
+
+        let mut accel = Lsm303agr::new(MockI2c { regs }).ok().unwrap();
+        let sample = accel.read().ok().unwrap();
+        assert_eq!(
+            sample,
+            Sample {
+                x: -16,
+                y: -32,
+                z: 1000
+            }
+        );
+        assert_eq!(Orientation::of(&sample), Orientation::FaceUp);
+
+        let bus = accel.release();
+        assert_eq!(bus.regs[0x20], 0x57);
+        assert_eq!(bus.regs[0x23], 0x98);
+    }
+
+    #[test]
+    fn accelerometer_rejects_unknown_device() {
+        let mut regs = [0u8; 0x40];
+        regs[0x0F] = 0x32;
+        assert!(Lsm303agr::new(MockI2c { regs }).is_err());
+    }
+
+    #[test]
+    fn flip_detector_debounces() {
+        let up = Sample {
+            x: 0,
+            y: 0,
+            z: 1000,
+        };
+        let down = Sample {
+            x: 0,
+            y: 0,
+            z: -1000,
+        };
+        let edge = Sample {
+            x: 1000,
+            y: 0,
+            z: 0,
+        };
+        let mut flips = FlipDetector::new(Duration::from_millis(500), Instant::from_millis(0));
+        let mut run = |sample: &Sample, from: u64, to: u64| {
+            let mut last = None;
+            for t in (from..to).step_by(100) {
+                if let Some(o) = flips.update(sample, Instant::from_millis(t)) {
+                    assert!(last.is_none());
+                    last = Some(o);
+                }
+            }
+            last
+        };
+
+        // Lying face up at boot isn't a flip
+        assert_eq!(run(&up, 0, 1000), None);
+        // Not settled yet
+        assert_eq!(run(&down, 1000, 1400), None);
+        assert_eq!(run(&down, 1400, 2000), Some(Orientation::FaceDown));
+        // Picked up and put back
+        assert_eq!(run(&edge, 2000, 3000), None);
+        assert_eq!(run(&down, 3000, 4000), None);
+        assert_eq!(run(&up, 4000, 5000), Some(Orientation::FaceUp));
+    }
+
+    #[test]
+    fn frame_rotates_and_mirrors() {
+        let mut frame = ARROW_RIGHT;
+        frame.rotate(Rotation::R180);
+        assert!(frame == ARROW_LEFT);
+
+        #[rustfmt::skip]
+        let arrow_down = frame_5x5(&[
+            0b00100,
+            0b00100,
+            0b10101,
+            0b01110,
+            0b00100,
+        ]);
+        let mut frame = ARROW_RIGHT;
+        frame.rotate(Rotation::R90);
+        assert!(frame == arrow_down);
+        frame.rotate(Rotation::R270);
+        assert!(frame == ARROW_RIGHT);
+
+        let mut frame = ARROW_RIGHT;
+        frame.mirror();
+        assert!(frame == ARROW_LEFT);
+
+        let mut frame = CHECK_MARK;
+        frame.flip();
+        assert!(frame != CHECK_MARK);
+        frame.flip();
+        assert!(frame == CHECK_MARK);
+    }
+
+    #[test]
+    fn rotation_has_hysteresis() {
+        let mut rotation = RotationDetector::new();
+        // Lying flat keeps the rotation
+        assert_eq!(
+            rotation.update(&Sample {
+                x: 0,
+                y: 0,
+                z: 1000
+            }),
+            None
+        );
+        // Just past the diagonal
+        assert_eq!(
+            rotation.update(&Sample {
+                x: 600,
+                y: 500,
+                z: 0
+            }),
+            None
+        );
+        assert_eq!(
+            rotation.update(&Sample {
+                x: 900,
+                y: 500,
+                z: 0
+            }),
+            Some(Rotation::R90)
+        );
+        // Back just past the diagonal
+        assert_eq!(
+            rotation.update(&Sample {
+                x: 600,
+                y: 700,
+                z: 0
+            }),
+            None
+        );
+        assert_eq!(
+            rotation.update(&Sample {
+                x: 0,
+                y: -1000,
+                z: 0
+            }),
+            Some(Rotation::R180)
+        );
+    }
+
+    #[test]
+    fn touch_detects_short_and_long_touches() {
+        let mut touch = TouchDetector::new();
+        let mut t = 0;
+        // Feeds a charge time every 20 ms and returns the last touch
+        let mut feed = |touch: &mut TouchDetector, charge_time: u32, n: usize| {
+            let mut last = None;
+            for _ in 0..n {
+                last = touch.update(charge_time, Instant::from_millis(t)).or(last);
+                t += 20;
+            }
+            last
+        };
+
+        // Calibration
+        assert_eq!(feed(&mut touch, 100, 20), None);
+        // A glitch isn't a touch
+        assert_eq!(feed(&mut touch, 400, 2), None);
+        assert_eq!(feed(&mut touch, 100, 5), None);
+
+        assert_eq!(feed(&mut touch, 400, 10), None);
+        assert_eq!(feed(&mut touch, 100, 5), Some(Touch::Short));
+
+        // Reported while held, and not again on release
+        assert_eq!(feed(&mut touch, 400, 60), Some(Touch::Long));
+        assert_eq!(feed(&mut touch, 100, 5), None);
+    }
+
+    #[test]
+    fn settings_step_through_values() {
+        let mut settings = Settings::new(TimerConfig::DEFAULT);
+        assert_eq!(settings.field(), Field::Work);
+        settings.increase();
+        assert_eq!(settings.config().work_time, 30 * 60);
+        assert!(settings.frame() == fill_5x5(6, 25));
+
+        assert!(!settings.next());
+        assert_eq!(settings.field(), Field::Rest);
+        settings.decrease();
+        assert_eq!(settings.config().rest_time, 60);
+        settings.decrease();
+        assert_eq!(settings.config().rest_time, 60);
+        for _ in 0..20 {
+            settings.increase();
+        }
+        assert_eq!(settings.config().rest_time, 60 * 60);
+        assert!(settings.next());
+    }
+
+    #[test]
+    fn fill_rounds_up() {
+        #[rustfmt::skip]
+        let expected = frame_5x5(&[
+            0b11111,
+            0b11111,
+            0b11100,
+            0b00000,
+            0b00000,
+        ]);
+        assert!(fill_5x5(13 * 60, 25 * 60) == expected);
+        assert!(fill_5x5(1, 25 * 60) == fill_5x5(1, 25));
+        assert!(fill_5x5(0, 0) == Frame::empty());
+    }
+
+    #[test]
+    fn gray_fill_dims_the_last_led() {
+        // 13/25 of the way in, and half of the 14th LED
+        let frame = fill_5x5_gray(27, 50);
+        assert_eq!(frame.level(2, 2), MAX_LEVEL);
+        assert_eq!(frame.level(3, 2), 4);
+        assert_eq!(frame.level(4, 2), 0);
+        assert!(fill_5x5_gray(50, 50) == GrayFrame::from(fill_5x5(1, 1)));
+        assert!(fill_5x5_gray(0, 0) == GrayFrame::empty());
+    }
+
+    #[test]
+    fn auto_brightness_follows_light_smoothly() {
+        let mut auto = AutoBrightness::new(LightConfig::default());
+        assert_eq!(auto.update(2).level(), Brightness::MAX.level());
+
+        // Lights off: the display dims over a few measurements
+        let level = auto.update(512).level();
+        assert!(level > Brightness::MIN.level() && level < Brightness::MAX.level());
+        for _ in 0..20 {
+            auto.update(512);
+        }
+        assert_eq!(auto.update(512).level(), Brightness::MIN.level());
+
+        // Small changes around a level keep it
+        let mut auto = AutoBrightness::new(LightConfig::default());
+        assert_eq!(auto.update(32).level(), 5);
+        for discharge in [36, 29, 42, 26, 48] {
+            assert_eq!(auto.update(discharge).level(), 5);
+        }
+    }
+
+    /// Matrix pin that reads high for a number of reads after being switched to input
+    struct MockPin {
+        high: bool,
+        input: bool,
+        discharge: u32,
+        reads: Cell<u32>,
+    }
+
+    impl MockPin {
+        fn new(discharge: u32) -> Self {
+            MockPin {
+                high: false,
+                input: false,
+                discharge,
+                reads: Cell::new(0),
+            }
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for MockPin {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal::digital::OutputPin for MockPin {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.high = false;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.high = true;
+            Ok(())
+        }
+    }
+
+    impl SensePin for MockPin {
+        fn set_as_input(&mut self) {
+            self.input = true;
+            self.reads.set(0);
+        }
+
+        fn set_as_output(&mut self) {
+            self.input = false;
+        }
+
+        fn is_high(&self) -> bool {
+            if !self.input {
+                return self.high;
+            }
+            self.reads.set(self.reads.get() + 1);
+            self.reads.get() <= self.discharge
+        }
+    }
+
+    #[test]
+    fn matrix_times_the_slowest_column() {
+        let rows = core::array::from_fn(|_| MockPin::new(0));
+        let cols = core::array::from_fn(|i| MockPin::new(if i == 2 { 100 } else { 10 }));
+        let mut matrix: LedMatrix<MockPin, 5, 5> = LedMatrix::new(rows, cols);
+        assert_eq!(matrix.sense_light(), 100);
+
+        // Each measurement starts over
+        assert_eq!(matrix.sense_light(), 100);
+
+        // Measurements in the dark are capped
+        let rows = core::array::from_fn(|_| MockPin::new(0));
+        let cols = core::array::from_fn(|_| MockPin::new(u32::MAX));
+        let mut matrix: LedMatrix<MockPin, 5, 5> = LedMatrix::new(rows, cols);
+        assert_eq!(matrix.sense_light(), 1024);
+    }
+
+    #[test]
+    fn gray_scan_holds_bit_planes_by_weight() {
+        let rows = core::array::from_fn(|_| MockPin::new(0));
+        let cols = core::array::from_fn(|_| MockPin::new(0));
+        let mut matrix: LedMatrix<MockPin, 5, 5> = LedMatrix::new(rows, cols);
+        matrix.set_brightness(Brightness::MAX);
+        assert_eq!(matrix.mode(), ScanMode::Binary);
+        assert_eq!(matrix.render(), Duration::from_micros(500));
+
+        let mut frame = GrayFrame::empty();
+        frame.set(1, 0, 5);
+        matrix.apply_gray(frame);
+        assert_eq!(matrix.mode(), ScanMode::Gray);
+        for hold in [125, 250, 500, 125] {
+            assert_eq!(matrix.render(), Duration::from_micros(hold));
+        }
+
+        // Binary frames keep working
+        matrix.apply(Frame::empty());
+        assert_eq!(matrix.mode(), ScanMode::Binary);
+        assert_eq!(matrix.render(), Duration::from_micros(500));
+
+        // Rows start dark for a time that sets the brightness, without busy-waiting
+        matrix.set_brightness(Brightness::new(5));
+        assert_eq!(matrix.render(), Duration::from_micros(3000));
+        assert_eq!(matrix.render(), Duration::from_micros(500));
+    }
+
+    #[test]
+    fn transitions_end_on_the_new_frame() {
+        let (from, to) = (ARROW_RIGHT, CHECK_MARK);
+        for effect in [
+            Transition::SlideLeft,
+            Transition::SlideRight,
+            Transition::Wipe,
+            Transition::Dissolve,
+        ] {
+            let steps = effect.steps(5);
+            assert!(effect.frame(&from, &to, steps) == Image::Binary(to));
+        }
+        assert!(Transition::Fade.frame(&from, &to, 14) == Image::Gray(GrayFrame::from(to)));
+        assert!(Transition::Fade.frame(&from, &to, 7) == Image::Gray(GrayFrame::empty()));
+
+        // Halfway through a slide, the old frame has moved out by two columns
+        let mut expected = from;
+        expected.shift_left(2);
+        let mut incoming = to;
+        incoming.shift_right(3);
+        expected.or(&incoming);
+        assert!(Transition::SlideLeft.frame(&from, &to, 2) == Image::Binary(expected));
+
+        // A wipe shows the new frame left of the edge only
+        if let Image::Binary(wiped) = Transition::Wipe.frame(&fill_5x5(1, 1), &Frame::empty(), 2) {
+            assert!(!wiped.is_set(1, 0));
+            assert!(wiped.is_set(2, 0));
+        } else {
+            panic!("wipe is binary");
+        }
+    }
+
+    #[test]
+    fn clips_play_frames_for_their_durations() {
+        // Multiples of 125ms are whole ticks of the 32768 Hz time driver
+        let ms = Duration::from_millis;
+        const FRAMES: [Keyframe<5, 5>; 2] = [
+            Keyframe {
+                image: Image::Binary(ARROW_RIGHT),
+                duration: Duration::from_millis(250),
+            },
+            Keyframe {
+                image: Image::Binary(CHECK_MARK),
+                duration: Duration::from_millis(125),
+            },
+        ];
+        let mut animation = Animation {
+            frames: &FRAMES,
+            looping: false,
+        };
+        let once = Clip::Animation(animation);
+        assert!(once.frame_at(ms(125)) == Some((Image::Binary(ARROW_RIGHT), ms(125))));
+        assert!(once.frame_at(ms(250)) == Some((Image::Binary(CHECK_MARK), ms(125))));
+        assert!(once.frame_at(ms(375)).is_none());
+        animation.looping = true;
+        let looping = Clip::Animation(animation);
+        assert!(looping.frame_at(ms(875)) == Some((Image::Binary(ARROW_RIGHT), ms(125))));
+
+        // Transition steps split the duration, and the clip ends on the new frame
+        let slide = Clip::Transition {
+            from: ARROW_RIGHT,
+            to: CHECK_MARK,
+            effect: Transition::SlideLeft,
+            duration: ms(625),
+        };
+        let (image, remaining) = slide.frame_at(ms(500)).unwrap();
+        assert!(image == Image::Binary(CHECK_MARK));
+        assert_eq!(remaining, ms(125));
+        assert_eq!(slide.frame_at(ms(0)).unwrap().1, ms(125));
+        assert!(slide.frame_at(ms(625)).is_none());
+    }
+
+    #[test]
+    fn phase_sounds_mark_sets() {
+        assert_eq!(Buzzer::for_phase(State::Running, 0), Buzzer::WorkStart);
+        assert_eq!(Buzzer::for_phase(State::Resting, 1), Buzzer::RestStart);
+        assert_eq!(Buzzer::for_phase(State::Running, 1), Buzzer::WorkStart);
+        assert_eq!(Buzzer::for_phase(State::Resting, 4), Buzzer::LongRest);
+        assert_eq!(Buzzer::for_phase(State::Running, 4), Buzzer::Complete);
+        assert_eq!(Buzzer::for_phase(State::Resting, 5), Buzzer::RestStart);
+    }
+
+    #[test]
+    fn ringtones_replace_sounds() {
+        let mut ringtones = Ringtones::new();
+        assert_eq!(ringtones.get(Buzzer::RestStart), None);
+
+        let upload = b"\x01rest:d=8,o=5,b=160:g,e,4c";
+        assert!(matches!(ringtones.upload(upload), Ok(Buzzer::RestStart)));
+        assert_eq!(
+            ringtones.get(Buzzer::RestStart).as_deref(),
+            Some("rest:d=8,o=5,b=160:g,e,4c")
+        );
+        assert_eq!(ringtones.get(Buzzer::WorkStart), None);
+
+        // Survives a round trip through flash
+        let mut bytes = [0u8; Ringtones::SIZE];
+        bytes.copy_from_slice(ringtones.clone().as_mut_bytes());
+        let restored = Ringtones::from_bytes(bytes);
+        assert!(restored.get(Buzzer::RestStart).is_some());
+
+        // Empty text restores the built-in melody
+        assert!(matches!(ringtones.upload(b"\x01"), Ok(Buzzer::RestStart)));
+        assert_eq!(ringtones.get(Buzzer::RestStart), None);
+    }
+
+    #[test]
+    fn ringtone_uploads_are_validated() {
+        let mut ringtones = Ringtones::new();
+        assert!(matches!(
+            ringtones.upload(b""),
+            Err(ConfigError::UnknownSound(0xFF))
+        ));
+        assert!(matches!(
+            ringtones.upload(b"\x03x:d=4:c"),
+            Err(ConfigError::UnknownSound(3))
+        ));
+
+        // Positions are counted in the text, after the slot byte
+        let e = ringtones.upload(b"\x00x:d=4:c,z").unwrap_err();
+        let invalid_note = rtttl::Error {
+            kind: rtttl::ErrorKind::InvalidNote,
+            position: 8,
+        };
+        assert!(matches!(e, ConfigError::Ringtone(error) if error == invalid_note));
+        assert_eq!(ringtones.get(Buzzer::WorkStart), None);
+
+        let report = config_report(Err(&e));
+        assert_eq!(report[0], 7);
+        assert!(report[1..].ends_with(b" at byte 8"));
+
+        let long = [b'c'; ringtones::MAX_LEN + 2];
+        assert!(matches!(
+            ringtones.upload(&long),
+            Err(ConfigError::Ringtone(rtttl::Error {
+                kind: rtttl::ErrorKind::TooLong,
+                ..
+            }))
+        ));
+    }
+
+    /// Records the tones played, `None` for silence
+    #[derive(Default)]
+    struct MockPlayer {
+        tones: heapless::Vec<Option<u16>, 32>,
+    }
+
+    impl SoundPlayer for MockPlayer {
+        fn tone(&mut self, freq: u16, _duty: u16) {
+            self.tones.push(Some(freq)).unwrap();
+        }
+
+        fn silence(&mut self) {
+            self.tones.push(None).unwrap();
+        }
+    }
+
+    #[test]
+    fn sound_queue_orders_by_priority() {
+        let mut queue: SoundQueue<3> = SoundQueue::new();
+        assert_eq!(queue.push(Buzzer::WorkStart), None);
+        assert_eq!(queue.push(Buzzer::RestStart), None);
+        assert_eq!(queue.push(Buzzer::Error), None);
+        assert_eq!(queue.peek(), Some(Buzzer::Error));
+
+        // A full queue drops the newest sound unless it is more urgent than a waiting one
+        assert_eq!(queue.push(Buzzer::Complete), Some(Buzzer::Complete));
+        assert_eq!(queue.push(Buzzer::Error), Some(Buzzer::RestStart));
+        assert_eq!(queue.len(), 3);
+
+        assert_eq!(queue.pop(), Some(Buzzer::Error));
+        assert_eq!(queue.pop(), Some(Buzzer::Error));
+        assert_eq!(queue.pop(), Some(Buzzer::WorkStart));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn speaker_plays_notes_with_gaps() {
+        let mut speaker = Speaker::new(MockPlayer::default());
+        let start = Instant::from_millis(0);
+        assert_eq!(speaker.poll(start), None);
+
+        // Rest start: G5 and E5 eighth notes, then a C5 quarter note
+        speaker.apply(SoundRequest::Play(Buzzer::RestStart));
+        let eighth = Duration::from_millis(187);
+        let gap = Duration::from_millis(10);
+        assert_eq!(speaker.poll(start), Some(start + eighth - gap));
+        assert_eq!(speaker.poll(start + eighth - gap), Some(start + eighth));
+        assert_eq!(
+            speaker.poll(start + eighth),
+            Some(start + eighth + eighth - gap)
+        );
+        assert_eq!(speaker.playing(), Some(Buzzer::RestStart));
+
+        // Late polls catch up
+        assert_eq!(speaker.poll(Instant::from_secs(1)), None);
+        assert_eq!(speaker.playing(), None);
+        assert_eq!(
+            &speaker.player().tones[..],
+            &[Some(784), None, Some(660), None, Some(524), None]
+        );
+    }
+
+    #[test]
+    fn errors_pre_empt_and_cancel_stops() {
+        let mut speaker = Speaker::new(MockPlayer::default());
+        let start = Instant::from_millis(0);
+        speaker.apply(SoundRequest::Play(Buzzer::WorkStart));
+        speaker.poll(start);
+
+        // Sounds of the same priority wait their turn
+        speaker.apply(SoundRequest::Play(Buzzer::RestStart));
+        assert_eq!(speaker.playing(), Some(Buzzer::WorkStart));
+
+        speaker.apply(SoundRequest::Play(Buzzer::Error));
+        assert_eq!(speaker.playing(), None);
+        speaker.poll(start);
+        assert_eq!(speaker.playing(), Some(Buzzer::Error));
+        assert_eq!(speaker.player().tones.last(), Some(&Some(185)));
+
+        // The queued sound plays after the error
+        speaker.poll(Instant::from_secs(1));
+        assert_eq!(speaker.playing(), Some(Buzzer::RestStart));
+
+        speaker.apply(SoundRequest::Cancel);
+        assert_eq!(speaker.playing(), None);
+        assert_eq!(speaker.player().tones.last(), Some(&None));
+        assert_eq!(speaker.poll(Instant::from_secs(1)), None);
+    }
+
+    #[test]
+    fn volume_scales_duty() {
+        let full = Buzzer::WorkStart.duty();
+        assert_eq!(Volume::default().scale(full), full);
+        assert_eq!(Volume::new(2).scale(full), full / 4);
+        assert_eq!(Volume::new(1).scale(full), full / 16);
+        assert_eq!(Volume::MUTE.scale(full), 0);
+        assert!(Volume::MUTE.is_muted() && !Volume::new(1).is_muted());
+
+        // Erased flash reads as full volume
+        assert_eq!(Volume::new(0xFF), Volume::MAX);
+
+        // Button B steps down, and back to full volume after mute
+        let mut volume = Volume::MAX;
+        for level in [2, 1, 0, 3] {
+            volume = volume.next();
+            assert_eq!(volume.level(), level);
+        }
+
+        let report = config_report(Err(&ConfigError::InvalidVolume(4)));
+        assert_eq!(&report[..], b"\x09volume must be between 0 and 3");
+    }
+
+    #[test]
+    fn alarm_escalates_until_acknowledged() {
+        let start = Instant::from_secs(100);
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut alarm = Alarm::new(start);
+
+        // Three rings at each level, every 3, 2 and then 1 seconds
+        let rings = [
+            (0, 1),
+            (3, 1),
+            (6, 1),
+            (9, 2),
+            (11, 2),
+            (13, 2),
+            (15, 3),
+            (16, 3),
+            (17, 3),
+            (18, 3),
+        ];
+        for (secs, level) in rings {
+            assert_eq!(alarm.poll(at(secs)), Some(level));
+            assert_eq!(alarm.poll(at(secs)), None);
+        }
+        assert_eq!(alarm.duration(at(20)), Duration::from_secs(20));
+
+        // Each level is louder at any volume, up to the volume setting, and stays muted
+        let duty = |level, volume: Volume| {
+            let alarm = Buzzer::Alarm(level);
+            alarm.volume(volume).scale(alarm.duty())
+        };
+        for volume in [Volume::default(), Volume::new(1)] {
+            assert!(duty(1, volume) < duty(2, volume) && duty(2, volume) < duty(3, volume));
+            assert_eq!(duty(3, volume), volume.scale(Buzzer::Complete.duty()));
+        }
+        assert_eq!(duty(3, Volume::MUTE), 0);
+        assert!(Buzzer::Alarm(1).melody().notes != Buzzer::Alarm(3).melody().notes);
+    }
+
+    #[test]
+    fn sound_settings_survive_flash() {
+        // Erased flash
+        assert_eq!(
+            SoundSettings::from_bytes([0xFF; 4]),
+            SoundSettings::default()
+        );
+
+        let settings = SoundSettings {
+            volume: Volume::new(1),
+            alarm: true,
+            ..SoundSettings::default()
+        };
+        assert_eq!(SoundSettings::from_bytes(settings.to_bytes()), settings);
+    }
+
+    #[test]
+    fn ticks_follow_the_interval() {
+        let mut settings = SoundSettings::default();
+        assert!(!settings.ticks_at(10));
+
+        settings.set_tick(5);
+        assert_eq!(settings.tick_report(), 5);
+        assert!(settings.ticks_at(10));
+        assert!(!settings.ticks_at(9));
+
+        // Turning ticking off keeps the interval for the next time
+        settings.set_tick(0);
+        assert_eq!(settings.tick_report(), 0);
+        assert!(!settings.ticks_at(10));
+        settings.tick = true;
+        assert_eq!(SoundSettings::from_bytes(settings.to_bytes()), settings);
+        assert_eq!(settings.tick_report(), 5);
+
+        settings.set_tick(200);
+        assert_eq!(settings.tick_report(), MAX_TICK_INTERVAL);
+
+        // Ticks are quiet, don't flash and give way to any other sound
+        assert_eq!(Buzzer::Tick.volume(Volume::MAX), Volume::new(1));
+        assert_eq!(Buzzer::Tick.volume(Volume::MUTE), Volume::MUTE);
+        assert_eq!(Buzzer::Tick.flashes(), 0);
+        let mut queue: SoundQueue<2> = SoundQueue::new();
+        queue.push(Buzzer::Tick);
+        queue.push(Buzzer::Tick);
+        assert_eq!(queue.push(Buzzer::RestStart), Some(Buzzer::Tick));
+        assert_eq!(queue.pop(), Some(Buzzer::RestStart));
+    }
 }
@@ -0,0 +1,42 @@
+//! Ambient noise from the on-board microphone
+//!
+//! The microphone is sampled in short bursts. [`level`] turns a burst into a sound level and
+//! [`NoiseDetector`] tells when the room gets loud and quiet again. The main loop adds up the
+//! time the room is loud during work phases.
+use crate::types::NOISY;
+use defmt::*;
+use embassy_nrf::{
+    gpio::{AnyPin, Output},
+    saadc::Saadc,
+};
+use embassy_time::{Duration, Timer};
+pub use pomodoro_detect::{level, NoiseConfig, NoiseDetector};
+
+/// Time between two measurements
+const MEASURE_PERIOD: Duration = Duration::from_millis(100);
+/// Samples in a measurement
+const BURST_SIZE: usize = 128;
+
+/// Microphone task. The microphone stays powered as long as `_power` is held.
+#[embassy_executor::task]
+pub async fn noise_task(
+    mut mic: Saadc<'static, 1>,
+    _power: Output<'static, AnyPin>,
+    config: NoiseConfig,
+) {
+    let mut detector = NoiseDetector::new(config);
+    let mut samples = [0i16; BURST_SIZE];
+    loop {
+        Timer::after(MEASURE_PERIOD).await;
+        for sample in samples.iter_mut() {
+            let mut buf = [0; 1];
+            mic.sample(&mut buf).await;
+            *sample = buf[0];
+        }
+
+        if let Some(loud) = detector.update(level(&samples)) {
+            info!("noisy: {}", loud);
+            NOISY.lock(|n| n.set(loud));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Ringtones uploaded over BLE
+//!
+//! A ringtone replaces the built-in melody of a sound. Ringtones are kept as RTTTL text, see
+//! [`rtttl`](crate::rtttl), in the same layout in RAM and in flash: for each sound a length
+//! byte followed by the text. Erased flash reads as a length of `0xFF`, which means no
+//! ringtone.
+use crate::{
+    rtttl::{self, ErrorKind},
+    types::{Buzzer, ConfigError},
+};
+
+/// Maximum length of a ringtone in bytes
+pub const MAX_LEN: usize = 191;
+/// Size of a slot: the length byte and the text
+const SLOT_SIZE: usize = MAX_LEN + 1;
+/// Sounds that can be replaced, in slot order. The upload payload starts with the slot.
+const SOUNDS: [Buzzer; 3] = [Buzzer::WorkStart, Buzzer::RestStart, Buzzer::Error];
+
+/// Text of a ringtone
+pub type Text = heapless::String<MAX_LEN>;
+
+/// Uploaded ringtones
+#[derive(Clone)]
+#[repr(C, align(4))]
+pub struct Ringtones {
+    data: [u8; Self::SIZE],
+}
+
+impl Ringtones {
+    /// Size in flash. A multiple of 4 bytes, as flash is written in words.
+    pub const SIZE: usize = SOUNDS.len() * SLOT_SIZE;
+
+    /// No ringtones
+    pub const fn new() -> Self {
+        Ringtones {
+            data: [0xFF; Self::SIZE],
+        }
+    }
+
+    /// Ringtones read from flash
+    pub fn from_bytes(data: [u8; Self::SIZE]) -> Self {
+        Ringtones { data }
+    }
+
+    /// Bytes to write to flash
+    pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Copy of the ringtone for a sound, if one has been uploaded
+    pub fn get(&self, sound: Buzzer) -> Option<Text> {
+        let slot = self.slot(sound)?;
+        let len = slot[0] as usize;
+        if len == 0 || len > MAX_LEN {
+            return None;
+        }
+        let mut text = Text::new();
+        text.push_str(core::str::from_utf8(&slot[1..=len]).ok()?)
+            .ok()?;
+        Some(text)
+    }
+
+    /// Stores a ringtone from an upload: the slot of the sound (0 for work start, 1 for rest
+    /// start, 2 for errors) followed by RTTTL text. Empty text restores the built-in melody.
+    pub fn upload(&mut self, payload: &[u8]) -> Result<Buzzer, ConfigError> {
+        let (&index, text) = payload
+            .split_first()
+            .ok_or(ConfigError::UnknownSound(0xFF))?;
+        let sound = *SOUNDS
+            .get(index as usize)
+            .ok_or(ConfigError::UnknownSound(index))?;
+        if text.len() > MAX_LEN {
+            return Err(ConfigError::Ringtone(rtttl::Error {
+                kind: ErrorKind::TooLong,
+                position: MAX_LEN,
+            }));
+        }
+        if !text.is_empty() {
+            rtttl::parse(core::str::from_utf8(text)?)?;
+        }
+
+        let start = index as usize * SLOT_SIZE;
+        let slot = &mut self.data[start..start + SLOT_SIZE];
+        slot[0] = text.len() as u8;
+        slot[1..=text.len()].copy_from_slice(text);
+        Ok(sound)
+    }
+
+    fn slot(&self, sound: Buzzer) -> Option<&[u8]> {
+        let index = SOUNDS.iter().position(|s| *s == sound)?;
+        Some(&self.data[index * SLOT_SIZE..(index + 1) * SLOT_SIZE])
+    }
+}
+
+impl Default for Ringtones {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,2 @@
+//! RTTTL ringtone parser, see [`pomodoro_melody::rtttl`]
+pub use pomodoro_melody::rtttl::*;
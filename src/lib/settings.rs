@@ -0,0 +1,94 @@
+//! Settings mode: adjust the timer config on the device
+//!
+//! Button A and Button B decrease and increase the value in steps of 5 minutes. The display
+//! lights one LED per 5 minutes, filling from the top for the work time and from the bottom
+//! for the rest time. A touch moves on from the work time to the rest time, and then saves.
+use crate::{
+    display::{bitmaps::fill_5x5, Frame, Rotation},
+    types::TimerConfig,
+};
+use core::ops::RangeInclusive;
+
+/// Value being edited
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Field {
+    Work,
+    Rest,
+}
+
+/// Timer config being edited
+pub struct Settings {
+    work: u32,
+    rest: u32,
+    field: Field,
+}
+
+impl Settings {
+    /// Change per button press in minutes
+    pub const STEP: u32 = 5;
+
+    /// Starts editing the work time of a config
+    pub fn new(config: TimerConfig) -> Self {
+        Settings {
+            work: config.work_time / 60,
+            rest: config.rest_time / 60,
+            field: Field::Work,
+        }
+    }
+
+    /// Value being edited
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    /// The edited config
+    pub fn config(&self) -> TimerConfig {
+        TimerConfig {
+            work_time: self.work * 60,
+            rest_time: self.rest * 60,
+        }
+    }
+
+    /// Steps up to the next multiple of 5 minutes
+    pub fn increase(&mut self) {
+        let (value, range) = self.value();
+        *value = ((*value / Self::STEP + 1) * Self::STEP).min(*range.end());
+    }
+
+    /// Steps down to the previous multiple of 5 minutes
+    pub fn decrease(&mut self) {
+        let (value, range) = self.value();
+        *value = (value.saturating_sub(1) / Self::STEP * Self::STEP).max(*range.start());
+    }
+
+    /// Moves on to the next value. Returns `true` once all values are set.
+    pub fn next(&mut self) -> bool {
+        match self.field {
+            Field::Work => {
+                self.field = Field::Rest;
+                false
+            }
+            Field::Rest => true,
+        }
+    }
+
+    /// Frame showing the value being edited
+    pub fn frame(&self) -> Frame<5, 5> {
+        let max = 25 * Self::STEP;
+        match self.field {
+            Field::Work => fill_5x5(self.work, max),
+            Field::Rest => {
+                let mut frame = fill_5x5(self.rest, max);
+                frame.rotate(Rotation::R180);
+                frame
+            }
+        }
+    }
+
+    fn value(&mut self) -> (&mut u32, RangeInclusive<u32>) {
+        match self.field {
+            Field::Work => (&mut self.work, TimerConfig::WORK_MINUTES),
+            Field::Rest => (&mut self.rest, TimerConfig::REST_MINUTES),
+        }
+    }
+}
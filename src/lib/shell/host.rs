@@ -0,0 +1,79 @@
+//! Requests from a host tool such as `pomodoro-cli`
+use crate::types::{
+    AppCommand, ConfigError, TimerConfig, APP_COMMANDS, APP_STATUS, CONFIG_SAVED_SIGNAL,
+};
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Timer};
+use pomodoro_protocol::{
+    self as protocol, Command, Config, Device, Request, Response, Stats, Status,
+};
+
+/// How long a new config may take to be saved, well within the CLI's read timeout, so that a
+/// slow or failed save is answered as busy.
+const SAVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Runs a request from a host tool. A new config is answered once the main loop has saved it,
+/// so that a failed save reaches the host.
+pub async fn handle(request: Request) -> Response {
+    let saving = matches!(request, Request::SetConfig(_));
+    if saving {
+        // Drop results of configs set from the command line
+        CONFIG_SAVED_SIGNAL.reset();
+    }
+    match request.handle(&mut App) {
+        Response::Ok if saving => {
+            match select(CONFIG_SAVED_SIGNAL.wait(), Timer::after(SAVE_TIMEOUT)).await {
+                Either::First(Ok(())) => Response::Ok,
+                Either::First(Err(e)) => Response::Error(e),
+                Either::Second(_) => Response::Error(protocol::Error::Busy),
+            }
+        }
+        response => response,
+    }
+}
+
+/// Reports the result of saving a config from the serial port, see [`handle`]
+pub fn config_saved(result: Result<(), &ConfigError>) {
+    CONFIG_SAVED_SIGNAL.signal(result.map_err(protocol::Error::from));
+}
+
+/// The app as seen by the protocol handler. Changes are forwarded to the main loop.
+pub struct App;
+
+impl Device for App {
+    fn status(&self) -> Status {
+        let status = APP_STATUS.lock(|s| s.get());
+        Status {
+            state: status.state.into(),
+            remaining: status.remaining,
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        APP_STATUS.lock(|s| s.get()).stats.into()
+    }
+
+    fn config(&self) -> Config {
+        APP_STATUS.lock(|s| s.get()).config.into()
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), protocol::Error> {
+        let config = TimerConfig::try_from(config).map_err(|e| protocol::Error::from(&e))?;
+        forward(AppCommand::SetConfig(config))
+    }
+
+    fn command(&mut self, command: Command) -> Result<(), protocol::Error> {
+        forward(match command {
+            Command::Start => AppCommand::Start,
+            Command::Pause => AppCommand::Pause,
+            Command::Skip => AppCommand::Skip,
+        })
+    }
+}
+
+/// Forwards a command to the main loop
+fn forward(command: AppCommand) -> Result<(), protocol::Error> {
+    APP_COMMANDS
+        .try_send(command)
+        .map_err(|_| protocol::Error::Busy)
+}
@@ -0,0 +1,192 @@
+//! Serial shell on the USB serial port
+//!
+//! * Line editing with backspace, `Ctrl-U` and `Ctrl-C`
+//! * Commands are parsed by [`pomodoro_shell`] and forwarded to the main loop as [`AppCommand`]s
+//! * [`events`](crate::events) are written as JSON lines in between
+//! * [`pomodoro_protocol`] frames from a host tool are answered in place of command lines
+use crate::{
+    ble::advertising::ADV_STATS,
+    display::Brightness,
+    events::{ConfigSource, EVENTS},
+    types::{AppCommand, TimerConfig, APP_COMMANDS, APP_STATUS},
+};
+use core::fmt::Write;
+use defmt::*;
+use embassy_futures::join::join;
+use embassy_nrf::{
+    peripherals::UARTE0,
+    uarte::{Uarte, UarteRx, UarteTx},
+};
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use pomodoro_protocol::{self as protocol, FrameReader, Input, Request, Response, MAX_FRAME_SIZE};
+
+mod host;
+pub use host::*;
+pub use pomodoro_shell::{Command, Edit, LineEditor, ShellError, HELP};
+
+/// Maximum length of a command line
+const LINE_SIZE: usize = 64;
+/// Prompt shown before each command line
+const PROMPT: &[u8] = b"pomodoro> ";
+
+/// Response to a command
+type Output = heapless::String<1024>;
+/// Serial port TX, shared by the shell and the event stream
+type Tx = Mutex<NoopRawMutex, UarteTx<'static, UARTE0>>;
+
+/// Serial shell task
+#[embassy_executor::task]
+pub async fn shell_task(uart: Uarte<'static, UARTE0>) {
+    let (tx, rx) = uart.split();
+    let tx = Mutex::new(tx);
+    join(shell(rx, &tx), event_stream(&tx)).await;
+}
+
+/// Reads command lines and writes responses
+async fn shell(mut rx: UarteRx<'static, UARTE0>, tx: &Tx) {
+    let mut editor: LineEditor<LINE_SIZE> = LineEditor::new();
+    let mut frames: FrameReader<MAX_FRAME_SIZE> = FrameReader::new();
+    let mut byte = [0u8; 1];
+
+    write(tx, PROMPT).await;
+    loop {
+        if let Err(e) = rx.read(&mut byte).await {
+            error!("{:?}", e);
+            continue;
+        }
+
+        let byte = match frames.push(byte[0]) {
+            Input::Byte(b) => b,
+            Input::Pending => continue,
+            Input::Frame(frame) => {
+                let response = match protocol::decode::<Request>(frame) {
+                    Ok(request) => handle(request).await,
+                    Err(_) => Response::Error(protocol::Error::Malformed),
+                };
+                // All responses fit in a frame
+                let mut buf = [0u8; MAX_FRAME_SIZE];
+                if let Ok(response) = protocol::encode(&response, &mut buf) {
+                    write(tx, response).await;
+                }
+                continue;
+            }
+        };
+
+        match editor.push(byte) {
+            Edit::None => {}
+            Edit::Echo(b) => write(tx, &[b]).await,
+            Edit::Erase(n) => {
+                for _ in 0..n {
+                    write(tx, b"\x08 \x08").await;
+                }
+            }
+            Edit::Line => {
+                let mut out = Output::new();
+                if execute(editor.line(), &mut out).is_err() {
+                    warn!("shell output truncated");
+                }
+                let mut tx = tx.lock().await;
+                write_to(&mut tx, b"\r\n").await;
+                write_to(&mut tx, out.as_bytes()).await;
+                write_to(&mut tx, PROMPT).await;
+            }
+        }
+    }
+}
+
+/// Writes events as JSON lines
+async fn event_stream(tx: &Tx) {
+    loop {
+        let record = EVENTS.recv().await;
+        match record.to_line() {
+            Ok(line) => write(tx, &line).await,
+            Err(_) => warn!("event too large: {:?}", record),
+        }
+    }
+}
+
+/// Runs a command line and writes the response
+fn execute(line: &str, out: &mut Output) -> core::fmt::Result {
+    let command = match Command::parse(line) {
+        Ok(command) => command,
+        Err(e) => return write!(out, "error: {}\r\n", e.message()),
+    };
+
+    match command {
+        Command::Help => out.push_str(HELP).map_err(|_| core::fmt::Error),
+        Command::Status => {
+            let status = APP_STATUS.lock(|s| s.get());
+            write!(
+                out,
+                "state: {:?}\r\nremaining: {}:{:02}\r\n",
+                status.state,
+                status.remaining / 60,
+                status.remaining % 60
+            )
+        }
+        Command::GetConfig => {
+            let config = APP_STATUS.lock(|s| s.get()).config;
+            write!(
+                out,
+                "work: {} mins\r\nrest: {} mins\r\n",
+                config.work_time / 60,
+                config.rest_time / 60
+            )
+        }
+        Command::Stats => {
+            let stats = APP_STATUS.lock(|s| s.get()).stats;
+            let adv = ADV_STATS.lock(|s| s.get());
+            write!(
+                out,
+                "work phases: {}\r\nrest phases: {}\r\nfocus: {} mins\r\nnoisy: {} mins\r\nalarms: {} secs\r\n",
+                stats.work_completed,
+                stats.rest_completed,
+                stats.focus_time / 60,
+                stats.noisy_time / 60,
+                stats.alarm_time
+            )?;
+            write!(
+                out,
+                "advertising fast: {} ms\r\nadvertising slow: {} ms\r\nadvertising off: {} ms\r\nconnected: {} ms\r\n",
+                adv.fast_ms, adv.slow_ms, adv.off_ms, adv.connected_ms
+            )
+        }
+        Command::Start => send(AppCommand::Start, out),
+        Command::Pause => send(AppCommand::Pause, out),
+        Command::Skip => send(AppCommand::Skip, out),
+        Command::SetConfig(config) => match TimerConfig::try_from(config) {
+            Ok(config) => send(AppCommand::SetConfig(config), out),
+            Err(e) => write!(out, "error: {}\r\n", e.message()),
+        },
+        Command::Erase => send(AppCommand::EraseConfig, out),
+        Command::Brightness(level) => send(AppCommand::SetBrightness(Brightness::new(level)), out),
+        Command::AutoBrightness => send(AppCommand::AutoBrightness, out),
+        Command::Alarm(on) => send(AppCommand::SetAlarm(on), out),
+        Command::Tick(interval) => send(AppCommand::SetTick(interval, ConfigSource::Shell), out),
+        Command::Group(group) => send(AppCommand::SetGroup(group), out),
+    }
+}
+
+/// Forwards a command to the main loop
+fn send(command: AppCommand, out: &mut Output) -> core::fmt::Result {
+    match APP_COMMANDS.try_send(command) {
+        Ok(()) => write!(out, "ok\r\n"),
+        Err(_) => write!(out, "error: busy, try again\r\n"),
+    }
+}
+
+/// Writes to the serial port
+async fn write(tx: &Tx, data: &[u8]) {
+    write_to(&mut *tx.lock().await, data).await;
+}
+
+/// Writes to the serial port. The data is copied to RAM first as EasyDMA can't read from flash.
+async fn write_to(tx: &mut UarteTx<'static, UARTE0>, data: &[u8]) {
+    let mut buf = [0u8; 32];
+    for chunk in data.chunks(buf.len()) {
+        buf[..chunk.len()].copy_from_slice(chunk);
+        if let Err(e) = tx.write(&buf[..chunk.len()]).await {
+            error!("{:?}", e);
+        }
+    }
+}
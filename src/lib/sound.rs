@@ -0,0 +1,445 @@
+//! Sound requests for the speaker
+//!
+//! Tasks request sounds with [`play`] and stop them with [`cancel`]. The speaker task keeps
+//! waiting requests in a [`SoundQueue`], ordered by [`Priority`], and a [`Speaker`] plays them
+//! one note at a time on a [`SoundPlayer`]. A sound with a higher priority than the one playing
+//! cuts it short, so an error is heard straight away.
+//!
+//! The [`Volume`] scales the PWM duty. While muted, sounds are shown as LED flashes instead.
+use crate::{
+    display::{self, bitmaps::frame_5x5, Animation, Clip, Frame, Image, Keyframe},
+    melody::Note,
+    rtttl::{self, MAX_NOTES},
+    types::{Buzzer, RINGTONES, SOUND_REQUESTS, VOLUME},
+};
+use defmt::*;
+use embassy_futures::select::{select, Either};
+use embassy_nrf::{peripherals::PWM0, pwm::SimplePwm};
+use embassy_time::{Duration, Instant, Timer};
+
+/// Sounds waiting to be played
+pub const QUEUE_SIZE: usize = 4;
+/// Longest time between ticks in seconds
+pub const MAX_TICK_INTERVAL: u8 = pomodoro_protocol::MAX_TICK_INTERVAL;
+/// Silence at the end of each note, so repeated notes can be told apart
+const NOTE_GAP: Duration = Duration::from_millis(10);
+/// Most flashes in place of a sound
+const MAX_FLASHES: usize = 5;
+/// Flashes of the whole display, each followed by a short pause. A sound plays the first few.
+static FLASHES: [Keyframe<5, 5>; 2 * MAX_FLASHES] = flashes();
+
+/// How urgent a sound is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum Priority {
+    /// Dropped first from a full queue
+    Low,
+    Normal,
+    /// Pre-empts sounds of normal priority
+    High,
+}
+
+/// Request to the speaker task
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum SoundRequest {
+    Play(Buzzer),
+    /// Stop the current sound and drop the waiting ones
+    Cancel,
+}
+
+/// Speaker volume. The lowest level mutes the speaker.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Volume(u8);
+
+impl Volume {
+    /// Full volume
+    pub const MAX: Volume = Volume(3);
+
+    /// Muted
+    pub const MUTE: Volume = Volume(0);
+
+    /// Create a volume with a custom level
+    pub fn new(level: u8) -> Self {
+        Self(level.min(Self::MAX.0))
+    }
+
+    /// Return the level value
+    pub fn level(&self) -> u8 {
+        self.0
+    }
+
+    pub fn is_muted(&self) -> bool {
+        *self == Self::MUTE
+    }
+
+    /// One level quieter, and full volume after mute
+    pub fn next(self) -> Self {
+        match self.0 {
+            0 => Self::MAX,
+            level => Self(level - 1),
+        }
+    }
+
+    /// Scales a duty for full volume. Each level is a quarter of the duty of the one above.
+    pub fn scale(&self, duty: u16) -> u16 {
+        match self.0 {
+            0 => 0,
+            level => duty >> (2 * (Self::MAX.0 - level)),
+        }
+    }
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self::MAX
+    }
+}
+
+/// Sound settings saved to flash
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct SoundSettings {
+    pub volume: Volume,
+    /// Ring at the end of a phase until acknowledged, see [`alarm`](crate::alarm)
+    pub alarm: bool,
+    /// Tick while a work phase runs
+    pub tick: bool,
+    /// Seconds between ticks, from 1 to [`MAX_TICK_INTERVAL`]
+    pub tick_interval: u8,
+}
+
+impl SoundSettings {
+    /// Size in flash: a word
+    pub const SIZE: usize = 4;
+
+    /// Settings read from flash. Erased flash reads as the defaults.
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        SoundSettings {
+            volume: Volume::new(bytes[0]),
+            alarm: bytes[1] == 1,
+            tick: bytes[2] == 1,
+            tick_interval: match bytes[3] {
+                interval @ 1..=MAX_TICK_INTERVAL => interval,
+                _ => 1,
+            },
+        }
+    }
+
+    /// Bytes to write to flash
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        [
+            self.volume.level(),
+            self.alarm as u8,
+            self.tick as u8,
+            self.tick_interval,
+        ]
+    }
+
+    /// Turns ticking off with an interval of `0`, or on with the interval in seconds
+    pub fn set_tick(&mut self, interval: u8) {
+        self.tick = interval > 0;
+        if self.tick {
+            self.tick_interval = interval.min(MAX_TICK_INTERVAL);
+        }
+    }
+
+    /// Interval reported to clients: `0` while ticking is off
+    pub fn tick_report(&self) -> u8 {
+        if self.tick {
+            self.tick_interval
+        } else {
+            0
+        }
+    }
+
+    /// Whether to tick with `remaining` seconds left in a work phase
+    pub fn ticks_at(&self, remaining: u32) -> bool {
+        self.tick && remaining % self.tick_interval as u32 == 0
+    }
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        SoundSettings {
+            volume: Volume::MAX,
+            alarm: false,
+            tick: false,
+            tick_interval: 1,
+        }
+    }
+}
+
+/// Requests a sound, or LED flashes while muted. The request is dropped if the speaker task
+/// is behind.
+pub fn play(sound: Buzzer) {
+    if VOLUME.lock(|v| v.get()).is_muted() {
+        let count = (sound.flashes() as usize).min(MAX_FLASHES);
+        if count > 0 {
+            display::play(Clip::Animation(Animation {
+                frames: &FLASHES[..2 * count],
+                looping: false,
+            }));
+        }
+    } else if SOUND_REQUESTS.try_send(SoundRequest::Play(sound)).is_err() {
+        warn!("sound dropped: {:?}", sound);
+    }
+}
+
+/// Stops the current sound and drops the waiting ones
+pub fn cancel() {
+    SOUND_REQUESTS.try_send(SoundRequest::Cancel).ok();
+}
+
+/// Builds [`FLASHES`]
+const fn flashes() -> [Keyframe<5, 5>; 2 * MAX_FLASHES] {
+    let on = Keyframe {
+        image: Image::Binary(frame_5x5(&[0b11111; 5])),
+        duration: Duration::from_millis(150),
+    };
+    let off = Keyframe {
+        image: Image::Binary(Frame::empty()),
+        duration: Duration::from_millis(100),
+    };
+    let mut frames = [off; 2 * MAX_FLASHES];
+    let mut i = 0;
+    while i < MAX_FLASHES {
+        frames[2 * i] = on;
+        i += 1;
+    }
+    frames
+}
+
+/// Output for tones
+pub trait SoundPlayer {
+    /// Plays a tone at a frequency in Hz and a PWM duty
+    fn tone(&mut self, freq: u16, duty: u16);
+    /// Stops the tone
+    fn silence(&mut self);
+}
+
+/// Bounded queue of sounds, played highest priority first and in request order within a
+/// priority
+pub struct SoundQueue<const N: usize> {
+    sounds: heapless::Vec<Buzzer, N>,
+}
+
+impl<const N: usize> SoundQueue<N> {
+    pub const fn new() -> Self {
+        SoundQueue {
+            sounds: heapless::Vec::new(),
+        }
+    }
+
+    /// Queues a sound. A full queue makes room by dropping its latest sound of the lowest
+    /// priority, if that is lower than the new one's. Returns the sound that was dropped.
+    pub fn push(&mut self, sound: Buzzer) -> Option<Buzzer> {
+        let sound = self.sounds.push(sound).err()?;
+        let (index, lowest) = self
+            .sounds
+            .iter()
+            .enumerate()
+            .rev()
+            .min_by_key(|(_, s)| s.priority())?;
+        if lowest.priority() >= sound.priority() {
+            return Some(sound);
+        }
+        let dropped = self.sounds.remove(index);
+        self.sounds.push(sound).ok();
+        Some(dropped)
+    }
+
+    /// The sound that plays next
+    pub fn peek(&self) -> Option<Buzzer> {
+        self.next_index().map(|i| self.sounds[i])
+    }
+
+    /// Takes the sound that plays next
+    pub fn pop(&mut self) -> Option<Buzzer> {
+        self.next_index().map(|i| self.sounds.remove(i))
+    }
+
+    pub fn clear(&mut self) {
+        self.sounds.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sounds.is_empty()
+    }
+
+    /// Index of the first sound of the highest priority
+    fn next_index(&self) -> Option<usize> {
+        let mut next: Option<usize> = None;
+        for (i, sound) in self.sounds.iter().enumerate() {
+            match next {
+                Some(n) if self.sounds[n].priority() >= sound.priority() => (),
+                _ => next = Some(i),
+            }
+        }
+        next
+    }
+}
+
+impl<const N: usize> Default for SoundQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A sound being played
+struct Playing {
+    sound: Buzzer,
+    duty: u16,
+    tempo: u16,
+    notes: heapless::Vec<Note, MAX_NOTES>,
+    /// Current note
+    index: usize,
+    /// End of the current note
+    note_end: Instant,
+    /// When the current note goes quiet, if it is still sounding
+    gap_start: Option<Instant>,
+}
+
+impl Playing {
+    /// Loads the melody of a sound: its uploaded ringtone, or the built-in melody
+    fn new(sound: Buzzer) -> Self {
+        let text = RINGTONES.lock(|r| r.borrow().get(sound));
+        let (tempo, notes) = match text.as_deref().and_then(|t| rtttl::parse(t).ok()) {
+            Some(ringtone) => (ringtone.tempo, ringtone.notes),
+            None => {
+                let melody = sound.melody();
+                let mut notes = heapless::Vec::new();
+                notes.extend_from_slice(melody.notes).ok();
+                (melody.tempo, notes)
+            }
+        };
+        Playing {
+            sound,
+            duty: sound.volume(VOLUME.lock(|v| v.get())).scale(sound.duty()),
+            tempo,
+            notes,
+            index: 0,
+            note_end: Instant::from_ticks(0),
+            gap_start: None,
+        }
+    }
+
+    /// Starts a note. Returns `false` after the last note.
+    fn start_note(&mut self, index: usize, start: Instant, player: &mut impl SoundPlayer) -> bool {
+        let note = match self.notes.get(index) {
+            Some(note) => note,
+            None => return false,
+        };
+        let duration = note.duration(self.tempo);
+        self.index = index;
+        self.note_end = start + duration;
+        if note.is_rest() {
+            player.silence();
+            self.gap_start = None;
+        } else {
+            player.tone(note.freq, self.duty);
+            self.gap_start = Some(self.note_end - NOTE_GAP.min(duration));
+        }
+        true
+    }
+}
+
+/// Plays queued sounds on a player
+pub struct Speaker<P> {
+    player: P,
+    queue: SoundQueue<QUEUE_SIZE>,
+    playing: Option<Playing>,
+}
+
+impl<P: SoundPlayer> Speaker<P> {
+    pub fn new(player: P) -> Self {
+        Speaker {
+            player,
+            queue: SoundQueue::new(),
+            playing: None,
+        }
+    }
+
+    pub fn player(&self) -> &P {
+        &self.player
+    }
+
+    /// The sound being played
+    pub fn playing(&self) -> Option<Buzzer> {
+        self.playing.as_ref().map(|p| p.sound)
+    }
+
+    /// Handles a request. Call [`poll`](Self::poll) afterwards to start the next sound.
+    pub fn apply(&mut self, request: SoundRequest) {
+        match request {
+            SoundRequest::Play(sound) => {
+                if let Some(dropped) = self.queue.push(sound) {
+                    warn!("sound queue full, dropped {:?}", dropped);
+                }
+                let current = self.playing.as_ref().map(|p| p.sound.priority());
+                let next = self.queue.peek().map(|s| s.priority());
+                if current.is_some() && next > current {
+                    self.stop();
+                }
+            }
+            SoundRequest::Cancel => {
+                self.queue.clear();
+                self.stop();
+            }
+        }
+    }
+
+    /// Plays up to `now`. Returns when to poll again, or `None` when there is nothing to play.
+    pub fn poll(&mut self, now: Instant) -> Option<Instant> {
+        loop {
+            match self.playing.as_mut() {
+                None => {
+                    let mut playing = Playing::new(self.queue.pop()?);
+                    if playing.start_note(0, now, &mut self.player) {
+                        self.playing = Some(playing);
+                    }
+                }
+                Some(playing) => {
+                    if let Some(gap_start) = playing.gap_start {
+                        if now < gap_start {
+                            return Some(gap_start);
+                        }
+                        self.player.silence();
+                        playing.gap_start = None;
+                    }
+                    if now < playing.note_end {
+                        return Some(playing.note_end);
+                    }
+                    // Notes follow each other without drifting
+                    let (index, start) = (playing.index + 1, playing.note_end);
+                    if !playing.start_note(index, start, &mut self.player) {
+                        self.playing = None;
+                    }
+                }
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.playing.take().is_some() {
+            self.player.silence();
+        }
+    }
+}
+
+/// Speaker task
+#[embassy_executor::task]
+pub async fn speaker_task(pwm: SimplePwm<'static, PWM0>) {
+    let mut speaker = Speaker::new(pwm);
+    loop {
+        let request = match speaker.poll(Instant::now()) {
+            Some(at) => match select(Timer::at(at), SOUND_REQUESTS.recv()).await {
+                Either::First(_) => continue,
+                Either::Second(request) => request,
+            },
+            None => SOUND_REQUESTS.recv().await,
+        };
+        speaker.apply(request);
+    }
+}
@@ -0,0 +1,136 @@
+//! Touch logo
+//!
+//! The logo pad is pulled up by a large resistor. Touching it adds capacitance, so it takes
+//! longer to charge after being grounded. [`TouchDetector`] turns the charge times into short
+//! and long touches.
+use crate::types::{AppCommand, APP_COMMANDS};
+use defmt::*;
+use embassy_nrf::gpio::{AnyPin, Flex, OutputDrive, Pull};
+use embassy_time::{Duration, Instant, Timer};
+
+/// Time between two measurements
+const SAMPLE_PERIOD: Duration = Duration::from_millis(20);
+/// Upper bound of a measurement, in polling loops
+const MAX_CHARGE_TIME: u32 = 5000;
+
+/// A touch gesture
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Touch {
+    /// Released before it became a long touch
+    Short,
+    /// Held for [`TouchDetector::LONG_TOUCH`]. Reported while still touched.
+    Long,
+}
+
+/// Detects touches from charge times
+pub struct TouchDetector {
+    /// Charge time while not touched
+    baseline: u32,
+    /// Measurements summed up for the initial baseline
+    calibration: (u32, u8),
+    touched: bool,
+    /// Measurements in a row that disagree with `touched`
+    streak: u8,
+    since: Instant,
+    long_reported: bool,
+}
+
+impl TouchDetector {
+    /// Measurements averaged for the initial baseline. Don't touch the logo at boot.
+    pub const CALIBRATION: u8 = 16;
+    /// Measurements in a row needed to change between touched and released
+    pub const DEBOUNCE: u8 = 3;
+    /// Duration of a long touch
+    pub const LONG_TOUCH: Duration = Duration::from_millis(1000);
+    /// Smallest difference from the baseline that counts as a touch
+    const MIN_DELTA: u32 = 20;
+
+    pub const fn new() -> Self {
+        TouchDetector {
+            baseline: 0,
+            calibration: (0, 0),
+            touched: false,
+            streak: 0,
+            since: Instant::from_ticks(0),
+            long_reported: false,
+        }
+    }
+
+    /// Feeds a charge time
+    pub fn update(&mut self, charge_time: u32, now: Instant) -> Option<Touch> {
+        let (sum, n) = &mut self.calibration;
+        if *n < Self::CALIBRATION {
+            *sum += charge_time;
+            *n += 1;
+            self.baseline = *sum / *n as u32;
+            return None;
+        }
+
+        // Touched at 1.5x the baseline, released below 1.25x
+        let threshold = if self.touched {
+            self.baseline + (self.baseline / 4).max(Self::MIN_DELTA / 2)
+        } else {
+            self.baseline + (self.baseline / 2).max(Self::MIN_DELTA)
+        };
+        let touched = charge_time > threshold;
+
+        if touched != self.touched {
+            self.streak += 1;
+            if self.streak < Self::DEBOUNCE {
+                return None;
+            }
+            self.streak = 0;
+            self.touched = touched;
+            if touched {
+                self.since = now;
+                self.long_reported = false;
+                return None;
+            }
+            return (!self.long_reported).then_some(Touch::Short);
+        }
+        self.streak = 0;
+
+        if !self.touched {
+            // Follow slow drift, e.g. from temperature
+            self.baseline = (self.baseline * 15 + charge_time) / 16;
+        } else if !self.long_reported && now - self.since >= Self::LONG_TOUCH {
+            self.long_reported = true;
+            return Some(Touch::Long);
+        }
+        None
+    }
+}
+
+impl Default for TouchDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Grounds the pad, releases it and counts polling loops until it reads high
+fn charge_time(pad: &mut Flex<'static, AnyPin>) -> u32 {
+    pad.set_as_output(OutputDrive::Standard);
+    pad.set_low();
+    // Discharge for about 10 us at 64 MHz
+    cortex_m::asm::delay(640);
+    pad.set_as_input(Pull::None);
+
+    let mut count = 0;
+    while pad.is_low() && count < MAX_CHARGE_TIME {
+        count += 1;
+    }
+    count
+}
+
+/// Touch logo task
+#[embassy_executor::task]
+pub async fn touch_task(mut logo: Flex<'static, AnyPin>) {
+    let mut detector = TouchDetector::new();
+    loop {
+        Timer::after(SAMPLE_PERIOD).await;
+        if let Some(touch) = detector.update(charge_time(&mut logo), Instant::now()) {
+            info!("touch: {:?}", touch);
+            APP_COMMANDS.try_send(AppCommand::Touch(touch)).ok();
+        }
+    }
+}
@@ -1,40 +1,161 @@
-use crate::display;
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
+use crate::{
+    alarm,
+    ble::advertising::AdvEvent,
+    display,
+    events::ConfigSource,
+    melody::{self, Melody},
+    ringtones::Ringtones,
+    rtttl,
+    sound::{Priority, SoundRequest, Volume},
+    touch::Touch,
+};
+use core::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+    ops::RangeInclusive,
+};
+use embassy_sync::{
+    blocking_mutex::{raw::ThreadModeRawMutex, Mutex},
+    channel::Channel,
+    signal::Signal,
+};
+use pomodoro_protocol::{self as protocol, Request};
 use serde::{Deserialize, Serialize};
 
-/// Signal for speaker
-pub static SPEAKER_SIGNAL: Signal<ThreadModeRawMutex, Buzzer> = Signal::new();
+/// Maximum size of a payload received over BLE
+pub const MAX_PAYLOAD_SIZE: usize = 512;
+/// A payload received over BLE, either from a single write or reassembled from a chunked transfer
+pub type Payload = heapless::Vec<u8, MAX_PAYLOAD_SIZE>;
+
+/// Requests for the speaker task, see [`sound`](crate::sound)
+pub static SOUND_REQUESTS: Channel<ThreadModeRawMutex, SoundRequest, 8> = Channel::new();
 /// Signal for setting
-pub static CONFIG_SIGNAL: Signal<ThreadModeRawMutex, heapless::Vec<u8, 32>> = Signal::new();
+pub static CONFIG_SIGNAL: Signal<ThreadModeRawMutex, Payload> = Signal::new();
+/// Signal for reporting the result of a config request back to the BLE client
+pub static CONFIG_RESULT_SIGNAL: Signal<ThreadModeRawMutex, ConfigReport> = Signal::new();
+/// Result of saving a config sent over the serial port, see [`shell::handle`](crate::shell::handle)
+pub static CONFIG_SAVED_SIGNAL: Signal<ThreadModeRawMutex, Result<(), protocol::Error>> =
+    Signal::new();
+/// Wake-ups for the advertising policy. The latest state and beacon are kept in
+/// [`ADV_APP_STATE`](crate::ble::advertising::ADV_APP_STATE) and
+/// [`ADV_BEACON`](crate::ble::advertising::ADV_BEACON).
+pub static ADV_EVENTS: Channel<ThreadModeRawMutex, AdvEvent, 4> = Channel::new();
+/// Commands for the main loop
+pub static APP_COMMANDS: Channel<ThreadModeRawMutex, AppCommand, 4> = Channel::new();
+/// Snapshot of the app, published by the main loop
+pub static APP_STATUS: Mutex<ThreadModeRawMutex, Cell<AppStatus>> =
+    Mutex::new(Cell::new(AppStatus::new()));
+/// Latest frame for the display task, see [`display::show`]
+pub static DISPLAY_SIGNAL: Signal<ThreadModeRawMutex, display::Image<5, 5>> = Signal::new();
+/// Clip to play over the posted frames, or `None` to cancel it, see [`display::play`]
+pub static ANIMATION_SIGNAL: Signal<ThreadModeRawMutex, Option<display::Clip<'static, 5, 5>>> =
+    Signal::new();
+/// Brightness setting for the display task
+pub static BRIGHTNESS_SIGNAL: Signal<ThreadModeRawMutex, display::BrightnessSetting> =
+    Signal::new();
+/// Brightness of the display, published by the display task
+pub static DISPLAY_BRIGHTNESS: Mutex<ThreadModeRawMutex, Cell<display::Brightness>> =
+    Mutex::new(Cell::new(display::Brightness::MAX));
+/// Display rotation picked by the accelerometer
+pub static DISPLAY_ROTATION: Mutex<ThreadModeRawMutex, Cell<display::Rotation>> =
+    Mutex::new(Cell::new(display::Rotation::R0));
+/// Ringtones uploaded over BLE, saved to flash by the main loop
+pub static RINGTONES: Mutex<ThreadModeRawMutex, RefCell<Ringtones>> =
+    Mutex::new(RefCell::new(Ringtones::new()));
+/// Whether the room is loud, set by the microphone task
+pub static NOISY: Mutex<ThreadModeRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+/// Speaker volume, set by the main loop
+pub static VOLUME: Mutex<ThreadModeRawMutex, Cell<Volume>> = Mutex::new(Cell::new(Volume::MAX));
 
-/// Buzzer
+/// Sounds for the speaker
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
 pub enum Buzzer {
-    Start,
+    WorkStart,
+    RestStart,
+    /// Start of the rest after a set of work phases
+    LongRest,
+    /// Start of a new set
+    Complete,
     Error,
+    /// Ring of the alarm at a level, see [`alarm`](crate::alarm)
+    Alarm(u8),
+    /// Soft click while a work phase runs
+    Tick,
 }
 
 impl Buzzer {
-    pub fn freq(&self) -> u32 {
+    /// Sound for the start of a phase, given the work phases completed so far
+    pub fn for_phase(phase: State, work_completed: u32) -> Buzzer {
+        let set_done = work_completed > 0 && work_completed % melody::SET_SIZE == 0;
+        match phase {
+            State::Resting if set_done => Self::LongRest,
+            State::Resting => Self::RestStart,
+            _ if set_done => Self::Complete,
+            _ => Self::WorkStart,
+        }
+    }
+
+    /// Built-in melody
+    pub fn melody(&self) -> &'static Melody<'static> {
         match self {
-            Self::Start => 880,
-            Self::Error => 185,
+            Self::WorkStart => &melody::WORK_START,
+            Self::RestStart => &melody::REST_START,
+            Self::LongRest => &melody::LONG_REST,
+            Self::Complete => &melody::COMPLETE,
+            Self::Error => &melody::ERROR,
+            Self::Alarm(level) => {
+                &melody::ALARM[((*level).clamp(1, alarm::MAX_LEVEL) - 1) as usize]
+            }
+            Self::Tick => &melody::TICK,
         }
     }
 
+    /// Duty at full volume: half of the PWM's max duty. The alarm starts at a fraction of it
+    /// and reaches it at its top level.
     pub fn duty(&self) -> u16 {
-        16383
+        const FULL: u16 = 16383;
+        match self {
+            Self::Alarm(level) => {
+                let level = (*level).clamp(1, alarm::MAX_LEVEL) as u32;
+                (FULL as u32 * level / alarm::MAX_LEVEL as u32) as u16
+            }
+            _ => FULL,
+        }
+    }
+
+    /// Volume to play at. Ticks are always quiet.
+    pub fn volume(&self, volume: Volume) -> Volume {
+        match self {
+            Self::Tick => Volume::new(volume.level().min(1)),
+            _ => volume,
+        }
     }
 
-    pub fn duration_ms(&self) -> u64 {
+    /// Flashes of the display in place of the sound while muted. Ticks don't flash.
+    pub fn flashes(&self) -> u8 {
         match self {
-            Self::Start => 200,
-            Self::Error => 500,
+            Self::WorkStart => 1,
+            Self::RestStart => 2,
+            Self::LongRest | Self::Complete => 3,
+            Self::Error => 5,
+            Self::Alarm(level) => *level,
+            Self::Tick => 0,
+        }
+    }
+
+    /// Errors pre-empt other sounds, and any other sound pre-empts a tick
+    pub fn priority(&self) -> Priority {
+        match self {
+            Self::Error => Priority::High,
+            Self::Tick => Priority::Low,
+            _ => Priority::Normal,
         }
     }
 }
 
 /// App's state
-#[derive(Copy, Clone, Debug, defmt::Format)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, defmt::Format)]
+#[serde(rename_all = "snake_case")]
 pub enum State {
     Paused,
     Running,
@@ -69,8 +190,18 @@ impl State {
     }
 }
 
+impl From<State> for protocol::State {
+    fn from(state: State) -> Self {
+        match state {
+            State::Paused => protocol::State::Paused,
+            State::Running => protocol::State::Running,
+            State::Resting => protocol::State::Resting,
+        }
+    }
+}
+
 /// Timer Configuration
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
 pub struct TimerConfig {
     /// Work time in seconds
     pub work_time: u32,
@@ -80,10 +211,7 @@ pub struct TimerConfig {
 
 impl Default for TimerConfig {
     fn default() -> Self {
-        TimerConfig {
-            work_time: 25 * 60,
-            rest_time: 5 * 60,
-        }
+        TimerConfig::DEFAULT
     }
 }
 
@@ -99,6 +227,51 @@ impl defmt::Format for TimerConfig {
 }
 
 impl TimerConfig {
+    /// Valid work times in minutes
+    pub const WORK_MINUTES: RangeInclusive<u32> = protocol::WORK_MINUTES;
+    /// Valid rest times in minutes
+    pub const REST_MINUTES: RangeInclusive<u32> = protocol::REST_MINUTES;
+    /// 25 mins of work and 5 mins of rest
+    pub const DEFAULT: TimerConfig = TimerConfig {
+        work_time: 25 * 60,
+        rest_time: 5 * 60,
+    };
+
+    /// Creates a config from work and rest times in minutes
+    pub fn from_minutes(work: u32, rest: u32) -> Result<TimerConfig, ConfigError> {
+        if !Self::WORK_MINUTES.contains(&work) {
+            return Err(ConfigError::WorkOutOfRange(work));
+        }
+        if !Self::REST_MINUTES.contains(&rest) {
+            return Err(ConfigError::RestOutOfRange(rest));
+        }
+        Ok(TimerConfig {
+            work_time: work * 60,
+            rest_time: rest * 60,
+        })
+    }
+
+    /// Parses a `"work,rest"` string with both times in minutes, or a framed
+    /// [`Request::SetConfig`]
+    pub fn parse(data: &Payload) -> Result<TimerConfig, ConfigError> {
+        if data.last() == Some(&protocol::DELIMITER) {
+            return match protocol::decode(&mut data.clone()) {
+                Ok(Request::SetConfig(config)) => config.try_into(),
+                _ => Err(ConfigError::InvalidFormat(data.len())),
+            };
+        }
+
+        let mut timers = data.split(|b| b == &b',');
+
+        if let (Some(w), Some(r)) = (timers.next(), timers.next()) {
+            let work = core::str::from_utf8(w)?.parse::<u32>()?;
+            let rest = core::str::from_utf8(r)?.parse::<u32>()?;
+            Self::from_minutes(work, rest)
+        } else {
+            Err(ConfigError::InvalidFormat(data.len()))
+        }
+    }
+
     /// Returns a timer for a specified `State`
     pub fn timer_for(&self, state: State) -> u32 {
         use State::*;
@@ -110,12 +283,209 @@ impl TimerConfig {
     }
 }
 
+impl TryFrom<protocol::Config> for TimerConfig {
+    type Error = ConfigError;
+
+    fn try_from(config: protocol::Config) -> Result<Self, Self::Error> {
+        Self::from_minutes(config.work_minutes, config.rest_minutes)
+    }
+}
+
+impl From<TimerConfig> for protocol::Config {
+    fn from(config: TimerConfig) -> Self {
+        protocol::Config {
+            work_minutes: config.work_time / 60,
+            rest_minutes: config.rest_time / 60,
+        }
+    }
+}
+
+/// Commands for the main loop, e.g. from the serial shell
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub enum AppCommand {
+    /// Start or resume a work phase
+    Start,
+    /// Pause a work phase
+    Pause,
+    /// Skip to the next phase
+    Skip,
+    /// Acknowledge the alarm
+    Acknowledge,
+    /// The logo has been touched
+    Touch(Touch),
+    /// Save a new timer config to flash
+    SetConfig(TimerConfig),
+    /// Erase the timer config from flash
+    EraseConfig,
+    /// Set the display brightness
+    SetBrightness(#[defmt(Debug2Format)] display::Brightness),
+    /// Let the ambient light set the display brightness
+    AutoBrightness,
+    /// Save the uploaded ringtones to flash
+    SaveRingtones,
+    /// Set the speaker volume and save it to flash. Changes over BLE are reported back.
+    SetVolume(Volume, ConfigSource),
+    /// Turn the alarm at the end of a phase on or off and save it to flash
+    SetAlarm(bool),
+    /// Set the seconds between ticks during work, `0` for no ticks, and save it to flash.
+    /// Changes over BLE are reported back.
+    SetTick(u8, ConfigSource),
+    /// Set the group id of group mode and save it to flash
+    SetGroup(u8),
+}
+
+/// Completed phases since boot
+#[derive(Clone, Copy, Debug, Default, defmt::Format)]
+pub struct SessionStats {
+    /// Completed work phases
+    pub work_completed: u32,
+    /// Completed rest phases
+    pub rest_completed: u32,
+    /// Time spent in work phases in seconds
+    pub focus_time: u32,
+    /// Time the room was loud during work phases in seconds
+    pub noisy_time: u32,
+    /// Time alarms rang before they were acknowledged in seconds
+    pub alarm_time: u32,
+}
+
+impl SessionStats {
+    /// Creates zeroed stats
+    pub const fn new() -> Self {
+        SessionStats {
+            work_completed: 0,
+            rest_completed: 0,
+            focus_time: 0,
+            noisy_time: 0,
+            alarm_time: 0,
+        }
+    }
+}
+
+impl From<SessionStats> for protocol::Stats {
+    fn from(stats: SessionStats) -> Self {
+        protocol::Stats {
+            work_completed: stats.work_completed,
+            rest_completed: stats.rest_completed,
+            focus_time: stats.focus_time,
+        }
+    }
+}
+
+/// Snapshot of the app
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct AppStatus {
+    /// Current state
+    pub state: State,
+    /// Remaining time of the current phase in seconds
+    pub remaining: u32,
+    /// Timer config in use
+    pub config: TimerConfig,
+    /// Display brightness level
+    pub brightness: u8,
+    /// Completed phases since boot
+    pub stats: SessionStats,
+}
+
+impl AppStatus {
+    /// Creates the status at boot, before a timer has started
+    pub const fn new() -> Self {
+        AppStatus {
+            state: State::Paused,
+            remaining: 0,
+            config: TimerConfig::DEFAULT,
+            brightness: 0,
+            stats: SessionStats::new(),
+        }
+    }
+}
+
 /// Possible Errors from configuration service
 #[derive(defmt::Format)]
 pub enum ConfigError {
-    InvalidFormat(heapless::Vec<u8, 32>),
+    /// Neither `"work,rest"` nor a framed request, with the length of the config
+    InvalidFormat(usize),
     Utf8(#[defmt(Debug2Format)] core::str::Utf8Error),
     Parse(#[defmt(Debug2Format)] core::num::ParseIntError),
+    WorkOutOfRange(u32),
+    RestOutOfRange(u32),
+    Storage,
+    Ringtone(rtttl::Error),
+    UnknownSound(u8),
+    InvalidVolume(u8),
+    InvalidTick(u8),
+}
+
+impl ConfigError {
+    /// Error code reported to the BLE client. `0` is reserved for success.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::InvalidFormat(_) => 1,
+            Self::Utf8(_) => 2,
+            Self::Parse(_) => 3,
+            Self::WorkOutOfRange(_) => 4,
+            Self::RestOutOfRange(_) => 5,
+            Self::Storage => 6,
+            Self::Ringtone(_) => 7,
+            Self::UnknownSound(_) => 8,
+            Self::InvalidVolume(_) => 9,
+            Self::InvalidTick(_) => 10,
+        }
+    }
+
+    /// Short message reported to the BLE client
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::InvalidFormat(_) => "expected \"work,rest\" in minutes",
+            Self::Utf8(_) => "config is not valid UTF-8",
+            Self::Parse(_) => "times must be whole numbers",
+            // The ranges are the protocol's, and so are their messages
+            Self::WorkOutOfRange(_) | Self::RestOutOfRange(_) => {
+                protocol::Error::from(self).message()
+            }
+            Self::Storage => "failed to save to flash",
+            Self::Ringtone(e) => e.kind.message(),
+            Self::UnknownSound(_) => "sound must be 0 (work), 1 (rest) or 2 (error)",
+            Self::InvalidVolume(_) => "volume must be between 0 and 3",
+            Self::InvalidTick(_) => "tick interval must be between 0 and 60 seconds",
+        }
+    }
+}
+
+/// Result of a config request as reported to the BLE client: an error code followed by a
+/// UTF-8 message. Code `0` with an empty message means success.
+pub type ConfigReport = heapless::Vec<u8, 64>;
+
+/// Builds the report for the result of a config request. Ringtone errors end with the
+/// position of the error.
+pub fn config_report(result: Result<(), &ConfigError>) -> ConfigReport {
+    let mut report = ConfigReport::new();
+    match result {
+        Ok(()) => {
+            report.push(0).ok();
+        }
+        Err(e) => {
+            report.push(e.code()).ok();
+            let mut message: heapless::String<63> = heapless::String::new();
+            message.push_str(e.message()).ok();
+            if let ConfigError::Ringtone(e) = e {
+                write!(message, " at byte {}", e.position).ok();
+            }
+            report.extend_from_slice(message.as_bytes()).ok();
+        }
+    }
+    report
+}
+
+impl From<&ConfigError> for protocol::Error {
+    fn from(e: &ConfigError) -> Self {
+        match e {
+            ConfigError::WorkOutOfRange(_) => protocol::Error::WorkOutOfRange,
+            ConfigError::RestOutOfRange(_) => protocol::Error::RestOutOfRange,
+            ConfigError::Storage => protocol::Error::Storage,
+            _ => protocol::Error::Malformed,
+        }
+    }
 }
 
 impl From<core::num::ParseIntError> for ConfigError {
@@ -124,6 +494,12 @@ impl From<core::num::ParseIntError> for ConfigError {
     }
 }
 
+impl From<rtttl::Error> for ConfigError {
+    fn from(e: rtttl::Error) -> Self {
+        ConfigError::Ringtone(e)
+    }
+}
+
 impl From<core::str::Utf8Error> for ConfigError {
     fn from(e: core::str::Utf8Error) -> Self {
         ConfigError::Utf8(e)
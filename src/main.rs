@@ -4,18 +4,62 @@
 
 use defmt::*;
 use embassy_executor::Spawner;
-use embassy_futures::select::{select, select3, Either, Either3};
-use embassy_nrf::{interrupt::Priority, pwm::SimplePwm};
-use embassy_time::{Duration, Timer};
+use embassy_futures::select::{select, select3, select4, Either, Either3, Either4};
+use embassy_nrf::{
+    gpio::{AnyPin, Input},
+    interrupt::Priority,
+};
+use embassy_time::{Duration, Instant, Timer};
 use microbit_pomodoro::{
-    self as _,
-    ble::{sd, server},
+    self as _, accel,
+    alarm::Alarm,
+    ble::{
+        advertising::{self, AdvEvent, AdvertisingPolicy},
+        sd, server,
+        sync::{self, GroupRole, SyncBeacon},
+    },
     device::Board,
-    display,
+    display::{self, bitmaps, BrightnessSetting, Clip, Transition},
+    events::{self, Button, ConfigSource, Event},
     flash_storage::FlashStorage,
-    types::{Buzzer, ConfigError, State, TimerConfig, CONFIG_SIGNAL, SPEAKER_SIGNAL},
+    noise,
+    ringtones::Ringtones,
+    settings::Settings,
+    shell,
+    sound::{self, SoundSettings},
+    touch::{self, Touch},
+    types::{
+        config_report, AppCommand, AppStatus, Buzzer, ConfigError, SessionStats, State,
+        TimerConfig, ADV_EVENTS, APP_COMMANDS, APP_STATUS, CONFIG_RESULT_SIGNAL, CONFIG_SIGNAL,
+        DISPLAY_BRIGHTNESS, NOISY, RINGTONES, VOLUME,
+    },
 };
-use nrf_softdevice::{Flash, Softdevice};
+use nrf_softdevice::Flash;
+
+/// Flash page holding the timer config
+const FS_START_ADDR: u32 = 0x7F000;
+const FS_END_ADDR: u32 = 0x80000;
+/// Flash page holding the uploaded ringtones
+const RINGTONES_START_ADDR: u32 = 0x7E000;
+const RINGTONES_END_ADDR: u32 = 0x7F000;
+/// Flash page holding the sound settings
+const SOUND_SETTINGS_START_ADDR: u32 = 0x7D000;
+const SOUND_SETTINGS_END_ADDR: u32 = 0x7E000;
+/// Flash page holding the group id of group mode
+const GROUP_START_ADDR: u32 = 0x7C000;
+const GROUP_END_ADDR: u32 = 0x7D000;
+/// Buttons pressed within this long of each other are pressed together
+const COMBO_WINDOW: Duration = Duration::from_millis(100);
+/// Length of the transition into a new phase
+const PHASE_TRANSITION: Duration = Duration::from_millis(500);
+/// Size of a serialized timer config
+const CONFIG_BUFF_SIZE: usize = 16;
+/// How long a touch shows the remaining time
+const SHOW_REMAINING: Duration = Duration::from_secs(2);
+/// Settings mode closes without saving after this long without input
+const SETTINGS_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time between two counts of the timer
+const TICK_PERIOD: Duration = Duration::from_secs(1);
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
@@ -26,6 +70,10 @@ async fn main(spawner: Spawner) {
     let p = embassy_nrf::init(config);
     let board = Board::init(p);
 
+    // Group mode: hold Button A at boot to lead, Button B to follow
+    let group_role = GroupRole::from_buttons(board.button1.is_low(), board.button2.is_low());
+    info!("group role: {:?}", group_role);
+
     // Enable SoftDevice
     let sd = nrf_softdevice::Softdevice::enable(&sd::softdevice_config());
     // Create BLE GATT server
@@ -33,140 +81,740 @@ async fn main(spawner: Spawner) {
     // Run SoftDevice task
     unwrap!(spawner.spawn(sd::softdevice_task(sd)));
     // Run BLE server task
-    unwrap!(spawner.spawn(server::ble_server_task(server, sd)));
+    let adv_policy = AdvertisingPolicy::default();
+    unwrap!(spawner.spawn(server::ble_server_task(server, sd, adv_policy)));
+    // Run observer task to follow a group leader
+    if group_role == GroupRole::Follower {
+        unwrap!(spawner.spawn(sync::observer_task(sd)));
+    }
 
     // Run Speaker task
-    let speaker = board.pwm;
-    unwrap!(spawner.spawn(speak(speaker)));
+    unwrap!(spawner.spawn(sound::speaker_task(board.pwm)));
+
+    // Run serial shell task
+    unwrap!(spawner.spawn(shell::shell_task(board.uart)));
+    events::emit(Event::Boot);
+
+    // Run accelerometer task: flip face-down to start, face-up to pause, shake to acknowledge
+    unwrap!(spawner.spawn(accel::accel_task(board.twim, accel::ShakeConfig::default())));
+
+    // Run display task: scan the LED matrix and follow the ambient light
+    unwrap!(spawner.spawn(display::display_task(board.display)));
+
+    // Run touch logo task: touch to show the remaining time, long touch for settings
+    unwrap!(spawner.spawn(touch::touch_task(board.logo)));
+
+    // Run microphone task: log how long the room is loud during work phases
+    let noise_config = noise::NoiseConfig::default();
+    unwrap!(spawner.spawn(noise::noise_task(board.mic, board.mic_power, noise_config)));
 
     // Button B: Start button
-    let start_button = board.button2;
+    let mut start_button = board.button2;
 
-    // Load timer config
-    let timer_config = load_timer_config(sd, start_button).await;
+    // Set up Flash Storage
+    let mut flash = Flash::take(sd);
+    let f_storage = FlashStorage::new(FS_START_ADDR, FS_END_ADDR);
+    let r_storage = FlashStorage::new(RINGTONES_START_ADDR, RINGTONES_END_ADDR);
+    let s_storage = FlashStorage::new(SOUND_SETTINGS_START_ADDR, SOUND_SETTINGS_END_ADDR);
+    let g_storage = FlashStorage::new(GROUP_START_ADDR, GROUP_END_ADDR);
+
+    // Load the group and the sounds, then the timer config
+    load_group(&mut flash, &g_storage).await;
+    load_ringtones(&mut flash, &r_storage).await;
+    let mut sound_settings = load_sound_settings(&mut flash, &s_storage).await;
+    let storage = (&f_storage, &r_storage, &s_storage, &g_storage);
+    let mut timer_config =
+        load_timer_config(&mut flash, storage, &mut sound_settings, &mut start_button).await;
     info!("config: {:?}", timer_config);
 
     // Init State
     let mut app_state = State::Running;
+    advertising::publish_state(app_state);
 
     // Init pomodoro timer
     let mut pomo_timer = timer_config.timer_for(app_state);
+    events::emit(Event::PhaseStarted {
+        phase: app_state,
+        duration: pomo_timer,
+    });
 
-    // Set up display
-    let mut display = board.display;
-    display.set_brightness(display::Brightness::MAX);
-
-    SPEAKER_SIGNAL.signal(Buzzer::Start);
+    sound::play(Buzzer::WorkStart);
 
     // Button A: Play/Pause button
     let mut play_pause_button = board.button1;
 
+    // Whether a follower's timer is in line with its group leader
+    let mut in_sync = false;
+
+    let mut stats = SessionStats::new();
+    // Time the room has been loud in the current work phase
+    let mut phase_noisy_time = 0;
+
+    // Show the remaining time instead of the state until then
+    let mut show_remaining_until = None;
+
+    // Alarm ringing at the end of a phase until it is acknowledged
+    let mut alarm: Option<Alarm> = None;
+
+    // Open settings mode, and when it closes without input
+    let mut settings: Option<(Settings, Instant)> = None;
+
+    // The timer counts down on a fixed 1 Hz deadline, so time spent handling buttons, commands
+    // and flash writes isn't added to each second
+    let mut next_tick = Instant::now() + TICK_PERIOD;
+
+    // Frame last posted to the display
+    let mut shown = None;
+
     loop {
-        // Load a bitmap for the app state
-        let bitmap = app_state.bitmap();
+        // Whether the current phase is over and the next one starts
+        let mut phase_over = false;
 
-        let timer_future = Timer::after(Duration::from_secs(1));
-        let button_future = play_pause_button.wait_for_falling_edge();
-        let display_future = display.display(bitmap, Duration::from_millis(1500));
+        APP_STATUS.lock(|s| {
+            s.set(AppStatus {
+                state: app_state,
+                remaining: pomo_timer,
+                config: timer_config,
+                brightness: DISPLAY_BRIGHTNESS.lock(|b| b.get()).level(),
+                stats,
+            })
+        });
 
-        match select3(timer_future, button_future, display_future).await {
+        // Load a bitmap for the app state, or the remaining time after a touch
+        let mut bitmap = match show_remaining_until {
+            Some(until) if Instant::now() < until => {
+                let phase = match app_state {
+                    State::Resting => State::Resting,
+                    _ => State::Running,
+                };
+                bitmaps::fill_5x5(pomo_timer, timer_config.timer_for(phase))
+            }
+            _ => app_state.bitmap(),
+        };
+        if in_sync {
+            bitmap.set(0, 0);
+        }
+        let noisy = NOISY.lock(|n| n.get());
+        if noise_config.indicator && noisy {
+            bitmap.set(4, 0);
+        }
+
+        let timer_future = Timer::at(next_tick);
+        let button_future = select(
+            play_pause_button.wait_for_falling_edge(),
+            start_button.wait_for_falling_edge(),
+        );
+        let command_future = APP_COMMANDS.recv();
+        let config_future = wait_for_new_config();
+        // Post only new frames, as each one restarts the display task's transitions
+        let frame = match &settings {
+            Some((s, _)) => s.frame(),
+            None => bitmap,
+        };
+        if shown != Some(frame) {
+            display::show(frame);
+            shown = Some(frame);
+        }
+
+        match select4(timer_future, button_future, command_future, config_future).await {
             // Timer expired
-            Either3::First(_) => match app_state {
-                State::Running | State::Resting => {
-                    pomo_timer -= 1;
-                    if pomo_timer == 0 {
+            Either4::First(_) => {
+                next_tick += TICK_PERIOD;
+                if matches!(settings, Some((_, until)) if Instant::now() >= until) {
+                    info!("settings timed out");
+                    settings = None;
+                }
+
+                match app_state {
+                    State::Running | State::Resting if alarm.is_none() => {
+                        if app_state == State::Running {
+                            stats.focus_time += 1;
+                            if noisy {
+                                stats.noisy_time += 1;
+                                phase_noisy_time += 1;
+                            }
+                        }
+                        pomo_timer -= 1;
+                        if pomo_timer == 0 {
+                            match app_state {
+                                State::Running => stats.work_completed += 1,
+                                _ => stats.rest_completed += 1,
+                            }
+                            let noisy_time =
+                                (app_state == State::Running).then_some(phase_noisy_time);
+                            events::emit(Event::Completed {
+                                phase: app_state,
+                                noisy_time,
+                            });
+                            if sound_settings.alarm {
+                                alarm = Some(Alarm::new(Instant::now()));
+                            } else {
+                                phase_over = true;
+                            }
+                        } else if app_state == State::Running && sound_settings.ticks_at(pomo_timer)
+                        {
+                            sound::play(Buzzer::Tick);
+                        }
+                    }
+                    _ => (),
+                }
+
+                match group_role {
+                    // Broadcast our timer
+                    GroupRole::Leader => {
+                        let beacon = SyncBeacon {
+                            group: sync::group(),
+                            state: app_state,
+                            remaining: pomo_timer,
+                        };
+                        advertising::publish_beacon(beacon);
+                    }
+                    // Line up with the leader, or keep local timing if it has disappeared
+                    GroupRole::Follower => {
+                        in_sync = false;
+                        if let Some((beacon, age)) = sync::last_beacon() {
+                            in_sync = true;
+                            if let Some((state, timer)) = beacon.align(age, app_state, pomo_timer) {
+                                if state != app_state {
+                                    // The leader has moved on
+                                    alarm = None;
+                                    advertising::publish_state(state);
+                                    events::emit(Event::PhaseStarted {
+                                        phase: state,
+                                        duration: timer,
+                                    });
+                                    phase_noisy_time = 0;
+                                    sound::play(Buzzer::for_phase(state, stats.work_completed));
+                                    animate_phase(app_state, state);
+                                }
+                                app_state = state;
+                                pomo_timer = timer;
+                            }
+                        }
+                    }
+                    GroupRole::Solo => (),
+                }
+            }
+            // Button A pressed
+            Either4::Second(Either::First(_)) => {
+                events::emit(Event::ButtonPressed { button: Button::A });
+                sound::cancel();
+                ADV_EVENTS.try_send(AdvEvent::ButtonPressed).ok();
+                if let Some(alarm) = alarm.take() {
+                    acknowledge(&alarm, &mut stats);
+                    phase_over = true;
+                } else if let Some((s, until)) = settings.as_mut() {
+                    s.decrease();
+                    *until = Instant::now() + SETTINGS_TIMEOUT;
+                } else if pressed_with(&start_button).await {
+                    sound_settings.tick = !sound_settings.tick;
+                    change_tick(&mut flash, &s_storage, &sound_settings)
+                        .await
+                        .ok();
+                } else {
+                    app_state = app_state.toggle();
+                    emit_toggle(app_state, pomo_timer);
+                    advertising::publish_state(app_state);
+                }
+            }
+            // Button B pressed: one level quieter, and full volume after mute. Both buttons
+            // together turn ticking on or off.
+            Either4::Second(Either::Second(_)) => {
+                events::emit(Event::ButtonPressed { button: Button::B });
+                sound::cancel();
+                ADV_EVENTS.try_send(AdvEvent::ButtonPressed).ok();
+                if let Some(alarm) = alarm.take() {
+                    acknowledge(&alarm, &mut stats);
+                    phase_over = true;
+                } else if let Some((s, until)) = settings.as_mut() {
+                    s.increase();
+                    *until = Instant::now() + SETTINGS_TIMEOUT;
+                } else if pressed_with(&play_pause_button).await {
+                    sound_settings.tick = !sound_settings.tick;
+                    change_tick(&mut flash, &s_storage, &sound_settings)
+                        .await
+                        .ok();
+                } else {
+                    sound_settings.volume = sound_settings.volume.next();
+                    change_volume(&mut flash, &s_storage, &sound_settings)
+                        .await
+                        .ok();
+                }
+            }
+            // Command from the serial shell
+            Either4::Third(command) => {
+                info!("command: {:?}", command);
+                if let AppCommand::Touch(_) = command {
+                    events::emit(Event::ButtonPressed {
+                        button: Button::Logo,
+                    });
+                }
+                match command {
+                    // Any of these acknowledges the alarm
+                    AppCommand::Acknowledge
+                    | AppCommand::Skip
+                    | AppCommand::Start
+                    | AppCommand::Pause
+                    | AppCommand::Touch(_)
+                        if alarm.is_some() =>
+                    {
+                        if let Some(alarm) = alarm.take() {
+                            acknowledge(&alarm, &mut stats);
+                            phase_over = true;
+                        }
+                    }
+                    AppCommand::Start if app_state == State::Paused => {
+                        app_state = State::Running;
+                        emit_toggle(app_state, pomo_timer);
+                    }
+                    AppCommand::Pause if app_state == State::Running => {
+                        app_state = State::Paused;
+                        emit_toggle(app_state, pomo_timer);
+                    }
+                    AppCommand::Skip if app_state != State::Paused => {
+                        animate_phase(app_state, app_state.next());
                         app_state = app_state.next();
+                        phase_noisy_time = 0;
                         pomo_timer = timer_config.timer_for(app_state);
-                        SPEAKER_SIGNAL.signal(Buzzer::Start);
+                        events::emit(Event::PhaseStarted {
+                            phase: app_state,
+                            duration: pomo_timer,
+                        });
+                        sound::play(Buzzer::for_phase(app_state, stats.work_completed));
+                    }
+                    AppCommand::SetConfig(config) => {
+                        // The new config applies from the next phase
+                        let source = ConfigSource::Shell;
+                        let result =
+                            change_timer_config(&mut flash, &f_storage, config, source).await;
+                        if result.is_ok() {
+                            timer_config = config;
+                        }
+                        shell::config_saved(result.as_ref().map(|_| ()));
+                    }
+                    // Settings mode: a touch moves on to the next field and saves after the
+                    // last one, a long touch closes without saving
+                    AppCommand::Touch(touch) if settings.is_some() => {
+                        let done = match (touch, settings.as_mut()) {
+                            (Touch::Short, Some((s, until))) => {
+                                *until = Instant::now() + SETTINGS_TIMEOUT;
+                                s.next().then(|| s.config())
+                            }
+                            _ => None,
+                        };
+                        if touch == Touch::Long || done.is_some() {
+                            settings = None;
+                        }
+                        if let Some(config) = done {
+                            let source = ConfigSource::Device;
+                            if change_timer_config(&mut flash, &f_storage, config, source)
+                                .await
+                                .is_ok()
+                            {
+                                timer_config = config;
+                                sound::play(Buzzer::WorkStart);
+                            }
+                        }
+                    }
+                    AppCommand::Touch(touch) => {
+                        display::cancel_animation();
+                        match touch {
+                            Touch::Short => {
+                                show_remaining_until = Some(Instant::now() + SHOW_REMAINING)
+                            }
+                            Touch::Long => {
+                                settings = Some((
+                                    Settings::new(timer_config),
+                                    Instant::now() + SETTINGS_TIMEOUT,
+                                ))
+                            }
+                        }
+                    }
+                    AppCommand::EraseConfig => {
+                        if let Err(e) = f_storage.erase(&mut flash).await {
+                            error!("{:?}", e);
+                        }
                     }
+                    AppCommand::SaveRingtones => save_ringtones(&mut flash, &r_storage).await,
+                    AppCommand::SetVolume(volume, source) => {
+                        sound_settings.volume = volume;
+                        let result = change_volume(&mut flash, &s_storage, &sound_settings).await;
+                        report_to_ble(source, result);
+                    }
+                    AppCommand::SetAlarm(on) => {
+                        sound_settings.alarm = on;
+                        if let Some(alarm) = alarm.take().filter(|_| !on) {
+                            acknowledge(&alarm, &mut stats);
+                            phase_over = true;
+                        }
+                        save_sound_settings(&mut flash, &s_storage, &sound_settings)
+                            .await
+                            .ok();
+                    }
+                    AppCommand::SetTick(interval, source) => {
+                        sound_settings.set_tick(interval);
+                        let result = change_tick(&mut flash, &s_storage, &sound_settings).await;
+                        report_to_ble(source, result);
+                    }
+                    AppCommand::SetGroup(group) => {
+                        change_group(&mut flash, &g_storage, group).await
+                    }
+                    AppCommand::Acknowledge => sound::cancel(),
+                    AppCommand::SetBrightness(brightness) => {
+                        display::set_brightness(BrightnessSetting::Fixed(brightness))
+                    }
+                    AppCommand::AutoBrightness => display::set_brightness(BrightnessSetting::Auto),
+                    _ => (),
+                }
+                advertising::publish_state(app_state);
+            }
+            // Config request received from BLE. The new config applies from the next phase.
+            Either4::Fourth(request) => {
+                if let Some(config) = save_ble_config(&mut flash, &f_storage, request).await {
+                    events::emit(Event::ConfigLoaded {
+                        source: ConfigSource::Ble,
+                        config,
+                    });
+                    timer_config = config;
                 }
-                _ => (),
-            },
-            // Button pressed
-            Either3::Second(_) => {
-                app_state = app_state.toggle();
             }
-            // Display timer expired (This never happens as timer_future or button_future always runs to completion first.)
-            Either3::Third(_) => {}
         };
-    }
-}
 
-/// Speaker task
-#[embassy_executor::task]
-async fn speak(mut speaker: SimplePwm<'static, embassy_nrf::peripherals::PWM0>) {
-    loop {
-        let buzz = SPEAKER_SIGNAL.wait().await;
-        speaker.set_period(buzz.freq());
-        speaker.set_duty(0, buzz.duty());
-        Timer::after(Duration::from_millis(buzz.duration_ms())).await;
-        speaker.set_duty(0, 0);
+        // Start the next phase once the last one is over, and acknowledged if the alarm is on
+        if phase_over {
+            animate_phase(app_state, app_state.next());
+            app_state = app_state.next();
+            phase_noisy_time = 0;
+            advertising::publish_state(app_state);
+            pomo_timer = timer_config.timer_for(app_state);
+            events::emit(Event::PhaseStarted {
+                phase: app_state,
+                duration: pomo_timer,
+            });
+            sound::play(Buzzer::for_phase(app_state, stats.work_completed));
+        }
+
+        if let Some(level) = alarm.as_mut().and_then(|a| a.poll(Instant::now())) {
+            sound::play(Buzzer::Alarm(level));
+        }
     }
 }
 
 /// Load a timer
 async fn load_timer_config(
-    sd: &Softdevice,
-    mut start_button: embassy_nrf::gpio::Input<'_, embassy_nrf::gpio::AnyPin>,
+    f: &mut Flash,
+    (f_storage, r_storage, s_storage, g_storage): (
+        &FlashStorage,
+        &FlashStorage,
+        &FlashStorage,
+        &FlashStorage,
+    ),
+    sound_settings: &mut SoundSettings,
+    start_button: &mut Input<'_, AnyPin>,
 ) -> TimerConfig {
-    // Set up Flash Storage
-    let mut f = Flash::take(sd);
-    const FS_START_ADDR: u32 = 0x7F000;
-    const FS_END_ADDR: u32 = 0x80000;
-    let f_storage = FlashStorage::new(FS_START_ADDR, FS_END_ADDR);
-
-    const BUFF_SIZE: usize = 16;
-    let mut buf = [0u8; BUFF_SIZE];
-
-    // Configure a new timer via BLE or hit Button B to start a pre-configured timer.
+    // Configure a new timer via BLE or the serial shell, or hit Button B to start a
+    // pre-configured timer.
+    let mut source = ConfigSource::Flash;
     loop {
         let button_future = start_button.wait_for_falling_edge();
         let config_future = wait_for_new_config();
+        let command_future = APP_COMMANDS.recv();
 
-        match select(button_future, config_future).await {
+        match select3(button_future, config_future, command_future).await {
             // Button pressed
-            Either::First(_) => break,
+            Either3::First(_) => {
+                events::emit(Event::ButtonPressed { button: Button::B });
+                sound::cancel();
+                ADV_EVENTS.try_send(AdvEvent::ButtonPressed).ok();
+                break;
+            }
             // Config request received from BLE
-            Either::Second(result) => match result {
-                // Valid request
-                Ok(config) => {
-                    if postcard::to_slice_cobs(&config, &mut buf).is_ok()
-                        && f_storage.write(&mut f, &mut buf).await.is_ok()
-                    {
-                        break;
+            Either3::Second(request) => {
+                if save_ble_config(f, f_storage, request).await.is_some() {
+                    source = ConfigSource::Ble;
+                    break;
+                }
+            }
+            // Command from the serial shell
+            Either3::Third(command) => match command {
+                AppCommand::Start => break,
+                AppCommand::SetConfig(config) => {
+                    let result = save_timer_config(f, f_storage, &config).await;
+                    shell::config_saved(result.as_ref().map(|_| ()));
+                    match result {
+                        Ok(()) => {
+                            source = ConfigSource::Shell;
+                            break;
+                        }
+                        Err(e) => {
+                            error!("{:?}", e);
+                            emit_config_error(&e);
+                        }
+                    }
+                }
+                AppCommand::EraseConfig => {
+                    if let Err(e) = f_storage.erase(f).await {
+                        error!("{:?}", e);
                     }
                 }
-                // Invalid request
-                Err(e) => {
-                    error!("{:?}", e);
-                    SPEAKER_SIGNAL.signal(Buzzer::Error);
+                AppCommand::SaveRingtones => save_ringtones(f, r_storage).await,
+                AppCommand::SetVolume(volume, source) => {
+                    sound_settings.volume = volume;
+                    report_to_ble(source, change_volume(f, s_storage, sound_settings).await);
                 }
+                AppCommand::SetAlarm(on) => {
+                    sound_settings.alarm = on;
+                    save_sound_settings(f, s_storage, sound_settings).await.ok();
+                }
+                AppCommand::SetTick(interval, source) => {
+                    sound_settings.set_tick(interval);
+                    report_to_ble(source, change_tick(f, s_storage, sound_settings).await);
+                }
+                AppCommand::SetGroup(group) => change_group(f, g_storage, group).await,
+                _ => (),
             },
         }
     }
 
     // Return a pre-defined timer if it exits. Otherwise, return a default timer.
-    if f_storage.read(&mut f, &mut buf).await.is_ok() {
-        match postcard::from_bytes_cobs(&mut buf) {
-            Ok(data) => data,
-            Err(_) => TimerConfig::default(),
-        }
+    let mut buf = [0u8; CONFIG_BUFF_SIZE];
+    let stored = if f_storage.read(f, &mut buf).await.is_ok() {
+        postcard::from_bytes_cobs(&mut buf).ok()
     } else {
+        None
+    };
+    let config = stored.unwrap_or_else(|| {
+        source = ConfigSource::Default;
         TimerConfig::default()
+    });
+    events::emit(Event::ConfigLoaded { source, config });
+    config
+}
+
+/// Saves a new timer config and reports it
+async fn change_timer_config(
+    f: &mut Flash,
+    f_storage: &FlashStorage,
+    config: TimerConfig,
+    source: ConfigSource,
+) -> Result<(), ConfigError> {
+    match save_timer_config(f, f_storage, &config).await {
+        Ok(()) => {
+            events::emit(Event::ConfigLoaded { source, config });
+            Ok(())
+        }
+        Err(e) => {
+            error!("{:?}", e);
+            emit_config_error(&e);
+            Err(e)
+        }
     }
 }
 
-/// Waits for a signal from BLE service
-async fn wait_for_new_config() -> Result<TimerConfig, ConfigError> {
-    let data = CONFIG_SIGNAL.wait().await;
-    let mut timers = data.split(|b| b == &b',');
+/// Saves a config requested over BLE and reports the result to the client. Returns the config
+/// once it is saved.
+async fn save_ble_config(
+    f: &mut Flash,
+    f_storage: &FlashStorage,
+    request: Result<TimerConfig, ConfigError>,
+) -> Option<TimerConfig> {
+    let result = match request {
+        // Valid request
+        Ok(config) => save_timer_config(f, f_storage, &config)
+            .await
+            .map(|()| config),
+        // Invalid request
+        Err(e) => Err(e),
+    };
+    CONFIG_RESULT_SIGNAL.signal(config_report(result.as_ref().map(|_| ())));
 
-    if let (Some(w), Some(r)) = (timers.next(), timers.next()) {
-        Ok(TimerConfig {
-            work_time: core::str::from_utf8(w)?.parse::<u32>()? * 60,
-            rest_time: core::str::from_utf8(r)?.parse::<u32>()? * 60,
-        })
+    match result {
+        Ok(config) => Some(config),
+        Err(e) => {
+            error!("{:?}", e);
+            emit_config_error(&e);
+            sound::play(Buzzer::Error);
+            None
+        }
+    }
+}
+
+/// Writes a timer config to flash
+async fn save_timer_config(
+    f: &mut Flash,
+    f_storage: &FlashStorage,
+    config: &TimerConfig,
+) -> Result<(), ConfigError> {
+    let mut buf = [0u8; CONFIG_BUFF_SIZE];
+    if postcard::to_slice_cobs(config, &mut buf).is_ok()
+        && f_storage.write(f, &mut buf).await.is_ok()
+    {
+        Ok(())
     } else {
-        Err(ConfigError::InvalidFormat(data))
+        Err(ConfigError::Storage)
     }
 }
+
+/// Loads the uploaded ringtones. Erased flash reads as no ringtones.
+async fn load_ringtones(f: &mut Flash, r_storage: &FlashStorage) {
+    let mut buf = [0u8; Ringtones::SIZE];
+    match r_storage.read(f, &mut buf).await {
+        Ok(()) => {
+            RINGTONES.lock(|r| r.replace(Ringtones::from_bytes(buf)));
+        }
+        Err(e) => error!("{:?}", e),
+    }
+}
+
+/// Writes the uploaded ringtones to flash and reports the result to the BLE client
+async fn save_ringtones(f: &mut Flash, r_storage: &FlashStorage) {
+    let mut ringtones = RINGTONES.lock(|r| r.borrow().clone());
+    let result = r_storage
+        .write(f, ringtones.as_mut_bytes())
+        .await
+        .map_err(|_| ConfigError::Storage);
+    if let Err(e) = &result {
+        error!("{:?}", e);
+    }
+    CONFIG_RESULT_SIGNAL.signal(config_report(result.as_ref().map(|_| ())));
+}
+
+/// Loads the sound settings. Erased flash reads as the defaults.
+async fn load_sound_settings(f: &mut Flash, s_storage: &FlashStorage) -> SoundSettings {
+    let mut buf = [0u8; SoundSettings::SIZE];
+    let settings = match s_storage.read(f, &mut buf).await {
+        Ok(()) => SoundSettings::from_bytes(buf),
+        Err(e) => {
+            error!("{:?}", e);
+            SoundSettings::default()
+        }
+    };
+    VOLUME.lock(|v| v.set(settings.volume));
+    settings
+}
+
+/// Applies the volume of the sound settings and saves them. Plays a sound at the new volume.
+async fn change_volume(
+    f: &mut Flash,
+    s_storage: &FlashStorage,
+    settings: &SoundSettings,
+) -> Result<(), ConfigError> {
+    VOLUME.lock(|v| v.set(settings.volume));
+    events::emit(Event::VolumeChanged {
+        level: settings.volume.level(),
+    });
+    let result = save_sound_settings(f, s_storage, settings).await;
+    // Let the new volume be heard, or seen while muted
+    sound::play(Buzzer::WorkStart);
+    result
+}
+
+/// Saves the sound settings after ticking is turned on or off. Plays a tick when on.
+async fn change_tick(
+    f: &mut Flash,
+    s_storage: &FlashStorage,
+    settings: &SoundSettings,
+) -> Result<(), ConfigError> {
+    events::emit(Event::TickChanged {
+        interval: settings.tick_report(),
+    });
+    let result = save_sound_settings(f, s_storage, settings).await;
+    if settings.tick {
+        sound::play(Buzzer::Tick);
+    }
+    result
+}
+
+/// Writes the sound settings to flash
+async fn save_sound_settings(
+    f: &mut Flash,
+    s_storage: &FlashStorage,
+    settings: &SoundSettings,
+) -> Result<(), ConfigError> {
+    let mut buf = settings.to_bytes();
+    let result = s_storage
+        .write(f, &mut buf)
+        .await
+        .map_err(|_| ConfigError::Storage);
+    if let Err(e) = &result {
+        error!("{:?}", e);
+    }
+    result
+}
+
+/// Loads the group id of group mode. Erased flash reads as group 0.
+async fn load_group(f: &mut Flash, g_storage: &FlashStorage) {
+    let mut buf = [0u8; sync::GROUP_SIZE];
+    let group = match g_storage.read(f, &mut buf).await {
+        Ok(()) => sync::group_from_bytes(buf),
+        Err(e) => {
+            error!("{:?}", e);
+            0
+        }
+    };
+    info!("group: {}", group);
+    sync::set_group(group);
+}
+
+/// Applies a group id of group mode and saves it to flash
+async fn change_group(f: &mut Flash, g_storage: &FlashStorage, group: u8) {
+    sync::set_group(group);
+    let mut buf = sync::group_to_bytes(group);
+    if let Err(e) = g_storage.write(f, &mut buf).await {
+        error!("{:?}", e);
+    }
+}
+
+/// Reports the result of a setting change to the BLE client, if the change came over BLE
+fn report_to_ble(source: ConfigSource, result: Result<(), ConfigError>) {
+    if let ConfigSource::Ble = source {
+        CONFIG_RESULT_SIGNAL.signal(config_report(result.as_ref().map(|_| ())));
+    }
+}
+
+/// Stops a ringing alarm and records how long it rang
+fn acknowledge(alarm: &Alarm, stats: &mut SessionStats) {
+    let duration = alarm.duration(Instant::now()).as_secs() as u32;
+    stats.alarm_time += duration;
+    events::emit(Event::AlarmAcknowledged { duration });
+    sound::cancel();
+}
+
+/// Whether another button is held shortly after a button press, for button combinations
+async fn pressed_with(other: &Input<'_, AnyPin>) -> bool {
+    Timer::after(COMBO_WINDOW).await;
+    other.is_low()
+}
+
+/// Slides the bitmap of a new phase in over the last one
+fn animate_phase(from: State, to: State) {
+    display::play(Clip::Transition {
+        from: from.bitmap(),
+        to: to.bitmap(),
+        effect: Transition::SlideLeft,
+        duration: PHASE_TRANSITION,
+    });
+}
+
+/// Emits a pause or resume event after a toggle
+fn emit_toggle(state: State, remaining: u32) {
+    match state {
+        State::Paused => events::emit(Event::Paused { remaining }),
+        State::Running => events::emit(Event::Resumed { remaining }),
+        State::Resting => (),
+    }
+}
+
+/// Emits a rejected config request
+fn emit_config_error(e: &ConfigError) {
+    events::emit(Event::ConfigError {
+        code: e.code(),
+        message: e.message(),
+    });
+}
+
+/// Waits for a signal from BLE service
+async fn wait_for_new_config() -> Result<TimerConfig, ConfigError> {
+    let data = CONFIG_SIGNAL.wait().await;
+    TimerConfig::parse(&data)
+}